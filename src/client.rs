@@ -1,111 +1,1715 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::str::FromStr;
+use std::sync::Mutex;
 use std::time::{Duration, SystemTime};
 
+use bitcoin::hashes::hmac::{Hmac, HmacEngine};
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPrivKey};
+use fedimint_client::oplog::OperationLogEntry;
 use fedimint_client::secret::{PlainRootSecretStrategy, RootSecretStrategy};
-use fedimint_client::Client;
+use fedimint_client::{Client, ClientHandle};
 use fedimint_core::api::InviteCode;
 use fedimint_core::config::ClientConfig;
 use fedimint_core::core::OperationId;
-use fedimint_core::db::{Database, IDatabaseTransactionOpsCore, IRawDatabase};
-use fedimint_core::task::spawn;
+use fedimint_core::db::{
+    Database, IDatabaseTransactionOpsCore, IDatabaseTransactionOpsCoreTyped, IRawDatabase,
+};
+use fedimint_core::module::__reexports::serde_json;
+use fedimint_core::task::{sleep, spawn, timeout};
+use fedimint_core::time::now;
 use fedimint_core::util::BoxStream;
 use fedimint_core::Amount;
 use fedimint_ln_client::{
-    LightningClientInit, LightningClientModule, LightningOperationMeta, LightningOperationMetaPay,
-    LightningOperationMetaVariant,
+    InternalPayState, LightningClientInit, LightningClientModule, LightningOperationMeta,
+    LightningOperationMetaPay, LightningOperationMetaVariant, LnPayState, LnReceiveState, PayType,
 };
+use fedimint_ln_common::config::FeeToAmount;
+use fedimint_ln_common::LightningGateway;
 use fedimint_mint_client::{
     MintClientInit, MintClientModule, MintOperationMeta, MintOperationMetaVariant, OOBNotes,
+    ReissueExternalNotesState, SpendOOBState,
 };
-use fedimint_wallet_client::WalletClientInit;
+use fedimint_wallet_client::{WalletClientInit, WalletClientModule};
 use futures::{StreamExt, TryFutureExt};
 use leptos::logging::warn;
-use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription};
+use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription, Currency, RoutingFees};
 use rand::thread_rng;
+use secp256k1_zkp::PublicKey;
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 use tokio::sync::{mpsc, oneshot, watch};
+use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::{debug, info};
 
-use crate::db::PersistentMemDb;
+use crate::db::{
+    EcashReclaimTimeoutKey, GatewayLastSeenKey, GatewayLastSeenKeyPrefix, GatewayVettingPolicy,
+    GatewayVettingPolicyKey, NoteConsolidationThresholdKey, OperationLabel, OperationLabelKey,
+    OperationLabelKeyPrefix, PersistentMemDb, PreferredGatewayKey,
+};
+
+#[derive(Debug, Clone)]
+enum RpcRequest {
+    ListWallets,
+    SelectWallet(String),
+    Join(String),
+    GetName,
+    SubscribeBalance,
+    EcashSend {
+        /// `None` spends all notes of the wallet
+        amount: Option<Amount>,
+        /// Overrides the wallet's default reclaim timeout
+        reclaim_after: Option<Duration>,
+        /// Exchange notes first if they can't represent the amount exactly
+        exact: bool,
+        /// Embed the federation's invite code so the recipient can join it
+        include_invite: bool,
+    },
+    PreviewEcash(String),
+    EcashReceive(String),
+    ReclaimEcash(OperationId),
+    GetEcashReclaimTimeout,
+    SetEcashReclaimTimeout(Duration),
+    PreviewInvoice {
+        invoice: String,
+        gateway_id: Option<PublicKey>,
+    },
+    /// Largest amount the balance can pay through the gateway after fees
+    MaxPayable {
+        gateway_id: Option<PublicKey>,
+    },
+    LnSend {
+        invoice: String,
+        gateway_id: Option<PublicKey>,
+    },
+    LnReceive {
+        amount: Amount,
+        description: InvoiceDescription,
+        expiry: Duration,
+        gateway_id: Option<PublicKey>,
+    },
+    ListTransactions(TransactionQuery),
+    SubscribeTransactions,
+    SetOperationLabel {
+        operation_id: OperationId,
+        label: OperationLabel,
+    },
+    BalanceHistory,
+    NoteHoldings,
+    ConsolidateNotes,
+    /// Whether notes are being consolidated, manually or automatically
+    SubscribeNoteConsolidation,
+    GetNoteConsolidationThreshold,
+    /// Number of notes above which they are consolidated automatically, `None`
+    /// turns automatic consolidation off
+    SetNoteConsolidationThreshold(Option<u64>),
+    ListGateways,
+    SetPreferredGateway(Option<PublicKey>),
+    SetGatewayVettingPolicy(GatewayVettingPolicy),
+    LnurlAuthSign {
+        domain: String,
+        k1: [u8; 32],
+    },
+}
+
+enum RpcResponse {
+    ListWallets(Vec<String>),
+    SelectWallet {
+        initialized: bool,
+    },
+    Join,
+    GetName(String),
+    SubscribeBalance(BoxStream<'static, Amount>),
+    EcashSend(OOBNotes),
+    PreviewEcash(EcashPreview),
+    EcashReceive {
+        operation_id: OperationId,
+        amount: Amount,
+        updates: BoxStream<'static, EcashReceiveProgress>,
+    },
+    /// Whether the notes were reissued into our wallet, `false` if the
+    /// recipient had already redeemed them
+    ReclaimEcash(bool),
+    GetEcashReclaimTimeout(Duration),
+    SetEcashReclaimTimeout,
+    PreviewInvoice(InvoicePreview),
+    MaxPayable(u64),
+    LnSend {
+        operation_id: OperationId,
+        updates: BoxStream<'static, LnPaymentState>,
+    },
+    LnReceive {
+        operation_id: OperationId,
+        invoice: String,
+        expires_at: SystemTime,
+        updates: BoxStream<'static, LnReceiveProgress>,
+    },
+    ListTransactions(TransactionPage),
+    SubscribeTransactions(BoxStream<'static, Transaction>),
+    SetOperationLabel,
+    BalanceHistory(Vec<BalancePoint>),
+    /// Number of notes held per denomination, smallest denomination first
+    NoteHoldings(Vec<(Amount, usize)>),
+    ConsolidateNotes(NoteConsolidation),
+    SubscribeNoteConsolidation(BoxStream<'static, bool>),
+    GetNoteConsolidationThreshold(Option<u64>),
+    SetNoteConsolidationThreshold,
+    ListGateways(GatewayList),
+    SetPreferredGateway,
+    SetGatewayVettingPolicy,
+    LnurlAuthSign {
+        key: String,
+        sig: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub timestamp: SystemTime,
+    pub operation_id: OperationId,
+    pub operation_kind: String,
+    pub direction: TransactionDirection,
+    pub amount_msat: i64,
+    pub fee_msat: u64,
+    pub status: TransactionStatus,
+    pub description: Option<String>,
+    /// Label the user attached to the operation
+    pub label: Option<String>,
+    pub tags: Vec<String>,
+    /// When unredeemed notes of an ecash spend are reclaimed automatically
+    pub reclaim_deadline: Option<SystemTime>,
+}
+
+impl Transaction {
+    pub fn set_label(&mut self, label: OperationLabel) {
+        self.label = Some(label.label).filter(|label| !label.is_empty());
+        self.tags = label.tags;
+    }
+
+    /// How much the transaction changed the wallet balance given its current
+    /// status. Outgoing funds are considered gone while the payment is still
+    /// pending, incoming ones only count once they were received.
+    fn balance_change_msat(&self) -> i64 {
+        match (self.direction, self.status) {
+            (TransactionDirection::Incoming, TransactionStatus::Success) => self.amount_msat,
+            (TransactionDirection::Incoming, _) => 0,
+            (
+                TransactionDirection::Outgoing,
+                TransactionStatus::Pending | TransactionStatus::Success,
+            ) => self.amount_msat - self.fee_msat as i64,
+            (TransactionDirection::Outgoing, _) => 0,
+        }
+    }
+}
+
+/// Wallet balance right after the operation at `timestamp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalancePoint {
+    pub timestamp: SystemTime,
+    pub balance_msat: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionDirection {
+    Incoming,
+    Outgoing,
+}
+
+impl std::fmt::Display for TransactionDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionDirection::Incoming => write!(f, "in"),
+            TransactionDirection::Outgoing => write!(f, "out"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TransactionStatus {
+    Pending,
+    Success,
+    Failed,
+    Refunded,
+    Canceled,
+    /// An invoice that wasn't paid before it expired
+    Expired,
+}
+
+impl std::fmt::Display for TransactionStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionStatus::Pending => write!(f, "pending"),
+            TransactionStatus::Success => write!(f, "success"),
+            TransactionStatus::Failed => write!(f, "failed"),
+            TransactionStatus::Refunded => write!(f, "refunded"),
+            TransactionStatus::Canceled => write!(f, "canceled"),
+            TransactionStatus::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// Restricts which transactions are returned by
+/// [`ClientRpc::list_transactions`], fields that are `None` match everything.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TransactionFilter {
+    pub operation_kind: Option<String>,
+    pub direction: Option<TransactionDirection>,
+    pub status: Option<TransactionStatus>,
+    /// Inclusive lower bound of the transaction timestamp
+    pub from: Option<SystemTime>,
+    /// Exclusive upper bound of the transaction timestamp
+    pub until: Option<SystemTime>,
+    /// Case-insensitive text that has to appear in the description, label or
+    /// one of the tags
+    pub search: Option<String>,
+}
+
+impl TransactionFilter {
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        let search_matches = |search: &String| {
+            let search = search.to_lowercase();
+            transaction
+                .description
+                .iter()
+                .chain(transaction.label.iter())
+                .chain(transaction.tags.iter())
+                .any(|text| text.to_lowercase().contains(&search))
+        };
+
+        self.operation_kind
+            .as_ref()
+            .map_or(true, |kind| kind == &transaction.operation_kind)
+            && self
+                .direction
+                .map_or(true, |direction| direction == transaction.direction)
+            && self
+                .status
+                .map_or(true, |status| status == transaction.status)
+            && self.from.map_or(true, |from| transaction.timestamp >= from)
+            && self
+                .until
+                .map_or(true, |until| transaction.timestamp < until)
+            && self.search.as_ref().map_or(true, search_matches)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionSort {
+    #[default]
+    Newest,
+    Oldest,
+    Largest,
+    Smallest,
+}
+
+impl TransactionSort {
+    fn sort(self, transactions: &mut [Transaction]) {
+        match self {
+            TransactionSort::Newest => {
+                transactions.sort_by_key(|tx| std::cmp::Reverse(tx.timestamp))
+            }
+            TransactionSort::Oldest => transactions.sort_by_key(|tx| tx.timestamp),
+            TransactionSort::Largest => {
+                transactions.sort_by_key(|tx| std::cmp::Reverse(tx.amount_msat.unsigned_abs()))
+            }
+            TransactionSort::Smallest => {
+                transactions.sort_by_key(|tx| tx.amount_msat.unsigned_abs())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionQuery {
+    pub filter: TransactionFilter,
+    pub sort: TransactionSort,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionPage {
+    pub transactions: Vec<Transaction>,
+    /// Number of transactions matching the query's filter across all pages
+    pub total: usize,
+}
+
+/// Lightning gateway registered with the federation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GatewayInfo {
+    pub gateway_id: PublicKey,
+    pub node_pub_key: PublicKey,
+    pub lightning_alias: String,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    /// Whether the federation vouches for the gateway in its meta data
+    pub vetted: bool,
+    /// Whether the wallet's vetting policy allows using the gateway
+    pub usable: bool,
+    pub last_seen: Option<SystemTime>,
+    /// Whether the user pinned the gateway for this wallet
+    pub preferred: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GatewayList {
+    pub gateways: Vec<GatewayInfo>,
+    pub vetting_policy: GatewayVettingPolicy,
+}
+
+/// Decoded out-of-band notes, shown to the user before redeeming them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EcashPreview {
+    pub notes: String,
+    pub total_amount: Amount,
+    /// Number of notes per denomination, smallest denomination first
+    pub denominations: Vec<(Amount, usize)>,
+    /// Notes only identify their federation by a prefix of its id
+    pub federation_id_prefix: String,
+    /// Whether the notes were issued by the open wallet's federation
+    pub same_federation: bool,
+    /// Invite code of the issuing federation, if the sender included it
+    pub federation_invite: Option<String>,
+}
+
+/// Outcome of reissuing the wallet's notes into fewer ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteConsolidation {
+    pub notes_before: usize,
+    pub notes_after: usize,
+}
+
+impl NoteConsolidation {
+    pub fn merged(&self) -> usize {
+        self.notes_before.saturating_sub(self.notes_after)
+    }
+}
+
+/// Redemption of out-of-band notes that was started
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EcashRedemption {
+    pub operation_id: OperationId,
+    pub amount: Amount,
+}
+
+/// Progress of redeeming out-of-band notes, which are only ours once the
+/// federation reissued them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EcashReceiveProgress {
+    Created,
+    Issuing,
+    Done,
+    Failed { reason: String },
+}
+
+impl EcashReceiveProgress {
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            EcashReceiveProgress::Done | EcashReceiveProgress::Failed { .. }
+        )
+    }
+}
+
+impl From<ReissueExternalNotesState> for EcashReceiveProgress {
+    fn from(state: ReissueExternalNotesState) -> Self {
+        match state {
+            ReissueExternalNotesState::Created => EcashReceiveProgress::Created,
+            ReissueExternalNotesState::Issuing => EcashReceiveProgress::Issuing,
+            ReissueExternalNotesState::Done => EcashReceiveProgress::Done,
+            ReissueExternalNotesState::Failed(reason) => EcashReceiveProgress::Failed { reason },
+        }
+    }
+}
+
+/// Decoded invoice together with what paying it would cost, shown to the user
+/// before any funds move
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvoicePreview {
+    pub invoice: String,
+    pub amount_msat: Option<u64>,
+    pub description: Option<String>,
+    /// Hex encoded hash of a description that isn't part of the invoice
+    pub description_hash: Option<String>,
+    pub payee: PublicKey,
+    pub expires_at: SystemTime,
+    /// Alias of the gateway that would be used, `None` if there is no usable
+    /// gateway and only internal payments are possible
+    pub gateway_alias: Option<String>,
+    pub estimated_fee_msat: u64,
+    pub balance_msat: u64,
+}
+
+impl InvoicePreview {
+    /// Balance left after the payment succeeded, negative if it is
+    /// insufficient
+    pub fn remaining_balance_msat(&self) -> i64 {
+        self.balance_msat as i64
+            - self.amount_msat.unwrap_or_default() as i64
+            - self.estimated_fee_msat as i64
+    }
+}
+
+/// Fee a gateway charges for paying `amount_msat`, rounded the same way
+/// fedimint does when funding the payment
+fn gateway_fee_msat(fees: &RoutingFees, amount_msat: u64) -> u64 {
+    // fedimint divides by `1_000_000 / proportional_millionths`, which is zero
+    // for fees above 100%
+    if fees.proportional_millionths > 1_000_000 {
+        let proportional_fee =
+            u128::from(amount_msat) * u128::from(fees.proportional_millionths) / 1_000_000;
+        return u64::try_from(proportional_fee)
+            .unwrap_or(u64::MAX)
+            .saturating_add(u64::from(fees.base_msat));
+    }
+    fees.to_amount(&Amount::from_msats(amount_msat)).msats
+}
+
+/// Largest amount `balance_msat` can pay including the gateway fee
+fn max_payable_msat(balance_msat: u64, fees: &RoutingFees) -> u64 {
+    let fits = |amount: u64| {
+        amount
+            .checked_add(gateway_fee_msat(fees, amount))
+            .is_some_and(|total| total <= balance_msat)
+    };
+
+    // fedimint charges `amount / (1_000_000 / proportional_millionths)`, the
+    // estimate can be off by a few msat due to rounding
+    let available = u128::from(balance_msat.saturating_sub(u64::from(fees.base_msat)));
+    let estimate = match u128::from(fees.proportional_millionths) {
+        0 => available,
+        ppm if ppm <= 1_000_000 => {
+            let divisor = 1_000_000 / ppm;
+            available * divisor / (divisor + 1)
+        }
+        ppm => available * 1_000_000 / (1_000_000 + ppm),
+    };
+    let mut amount = u64::try_from(estimate).expect("Not larger than the balance");
+    while amount > 0 && !fits(amount) {
+        amount -= 1;
+    }
+    while amount.checked_add(1).is_some_and(fits) {
+        amount += 1;
+    }
+    amount
+}
+
+/// Description of an invoice to create
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvoiceDescription {
+    /// Included in the invoice
+    Direct(String),
+    /// Only the hash is included in the invoice, the full text is stored
+    /// locally. Allows long descriptions and is required for LNURL and Nostr
+    /// zaps, where the payer commits to metadata it already knows.
+    Hash(String),
+}
+
+impl InvoiceDescription {
+    /// Maximum length of a direct description in bytes, limited by the size
+    /// of a BOLT11 tagged field
+    pub const MAX_DIRECT_LENGTH: usize = 639;
+
+    pub fn validate(&self) -> Result<(), InvoiceDescriptionError> {
+        match self {
+            InvoiceDescription::Direct(description)
+                if description.len() > Self::MAX_DIRECT_LENGTH =>
+            {
+                Err(InvoiceDescriptionError::TooLong {
+                    length: description.len(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, ThisError, PartialEq, Eq)]
+pub enum InvoiceDescriptionError {
+    #[error(
+        "The description is {length} bytes long, but invoices can only include {} bytes. Use a \
+         description hash instead.",
+        InvoiceDescription::MAX_DIRECT_LENGTH
+    )]
+    TooLong { length: usize },
+}
+
+/// Extra metadata stored with incoming Lightning payments
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LnReceiveExtraMeta {
+    /// Full description of invoices that only include its hash
+    #[serde(default)]
+    description: Option<String>,
+}
+
+/// Reclaim timeout of ecash spends if the wallet doesn't configure one
+pub const DEFAULT_ECASH_RECLAIM_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24 * 3);
+
+/// Extra metadata stored with out-of-band ecash spends
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EcashSpendExtraMeta {
+    #[serde(default)]
+    reclaim_deadline: Option<SystemTime>,
+}
+
+/// Progress of an outgoing Lightning payment, unifying the states of payments
+/// through a gateway and internal payments to users of the same federation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LnPaymentState {
+    Created,
+    Funded,
+    /// The gateway failed to pay the invoice, the funds are locked in the
+    /// contract until it times out
+    WaitingForRefund,
+    Success {
+        preimage: String,
+    },
+    Refunded {
+        reason: String,
+    },
+    Failed {
+        reason: String,
+    },
+}
+
+impl LnPaymentState {
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            LnPaymentState::Success { .. }
+                | LnPaymentState::Refunded { .. }
+                | LnPaymentState::Failed { .. }
+        )
+    }
+}
+
+impl From<LnPayState> for LnPaymentState {
+    fn from(state: LnPayState) -> Self {
+        match state {
+            LnPayState::Created => LnPaymentState::Created,
+            LnPayState::Funded | LnPayState::AwaitingChange => LnPaymentState::Funded,
+            LnPayState::WaitingForRefund { .. } => LnPaymentState::WaitingForRefund,
+            LnPayState::Success { preimage } => LnPaymentState::Success { preimage },
+            LnPayState::Refunded { gateway_error } => LnPaymentState::Refunded {
+                reason: gateway_error.to_string(),
+            },
+            LnPayState::Canceled => LnPaymentState::Failed {
+                reason: "Payment was canceled".to_owned(),
+            },
+            LnPayState::UnexpectedError { error_message } => LnPaymentState::Failed {
+                reason: error_message,
+            },
+        }
+    }
+}
+
+impl From<InternalPayState> for LnPaymentState {
+    fn from(state: InternalPayState) -> Self {
+        match state {
+            InternalPayState::Funding => LnPaymentState::Created,
+            InternalPayState::Preimage(preimage) => LnPaymentState::Success {
+                preimage: hex::encode(preimage.0),
+            },
+            InternalPayState::RefundSuccess { error, .. } => LnPaymentState::Refunded {
+                reason: error.to_string(),
+            },
+            InternalPayState::RefundError { error_message, .. } => LnPaymentState::Failed {
+                reason: format!("Refund failed: {error_message}"),
+            },
+            InternalPayState::FundingFailed { error } => LnPaymentState::Failed {
+                reason: error.to_string(),
+            },
+            InternalPayState::UnexpectedError(error_message) => LnPaymentState::Failed {
+                reason: error_message,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LnReceiveInvoice {
+    pub operation_id: OperationId,
+    pub invoice: String,
+    pub expires_at: SystemTime,
+}
+
+/// Progress of an incoming Lightning payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LnReceiveProgress {
+    Created,
+    WaitingForPayment,
+    /// The gateway funded the incoming contract
+    Funded,
+    /// Waiting for the e-cash issued for the payment
+    AwaitingFunds,
+    Claimed,
+    Canceled {
+        reason: String,
+    },
+}
+
+impl LnReceiveProgress {
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            LnReceiveProgress::Claimed | LnReceiveProgress::Canceled { .. }
+        )
+    }
+
+    /// Whether the invoice was paid, even if the funds aren't claimed yet
+    pub fn is_paid(&self) -> bool {
+        matches!(
+            self,
+            LnReceiveProgress::Funded
+                | LnReceiveProgress::AwaitingFunds
+                | LnReceiveProgress::Claimed
+        )
+    }
+}
+
+impl From<LnReceiveState> for LnReceiveProgress {
+    fn from(state: LnReceiveState) -> Self {
+        match state {
+            LnReceiveState::Created => LnReceiveProgress::Created,
+            LnReceiveState::WaitingForPayment { .. } => LnReceiveProgress::WaitingForPayment,
+            LnReceiveState::Funded => LnReceiveProgress::Funded,
+            LnReceiveState::AwaitingFunds => LnReceiveProgress::AwaitingFunds,
+            LnReceiveState::Claimed => LnReceiveProgress::Claimed,
+            LnReceiveState::Canceled { reason } => LnReceiveProgress::Canceled {
+                reason: reason.to_string(),
+            },
+        }
+    }
+}
+
+impl Debug for RpcResponse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RpcResponse::?")
+    }
+}
+
+#[derive(Debug, ThisError, Serialize, Deserialize, Clone)]
+pub enum RpcError {
+    #[error("Invalid response")]
+    InvalidResponse,
+    #[error("Client stopped")]
+    ClientStopped(String),
+    #[error("The federation didn't respond in time")]
+    FederationUnreachable,
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        if e.is::<FederationUnreachable>() {
+            return Self::FederationUnreachable;
+        }
+        Self::ClientStopped(e.to_string())
+    }
+}
+
+/// Returned by operations that need the federation when it doesn't respond
+#[derive(Debug, ThisError)]
+#[error("The federation didn't respond in time")]
+struct FederationUnreachable;
+
+type RpcCall = (RpcRequest, oneshot::Sender<anyhow::Result<RpcResponse>>);
+
+/// Signs the LNURL-auth challenge `k1` with the LUD-05 linking key for
+/// `domain`, which is derived from the wallet's root secret so the identity
+/// presented to a service stays the same. Returns the hex encoded public key
+/// and DER signature.
+async fn sign_lnurl_auth(
+    client: &Client,
+    domain: &str,
+    k1: [u8; 32],
+) -> anyhow::Result<(String, String)> {
+    let entropy = Client::load_decodable_client_secret::<[u8; 64]>(client.db()).await?;
+    let (public_key, signature) = lnurl_auth_signature(&entropy, domain, k1)?;
+
+    Ok((
+        hex::encode(public_key.serialize()),
+        hex::encode(signature.serialize_der()),
+    ))
+}
+
+fn lnurl_auth_signature(
+    entropy: &[u8; 64],
+    domain: &str,
+    k1: [u8; 32],
+) -> anyhow::Result<(
+    bitcoin::secp256k1::PublicKey,
+    bitcoin::secp256k1::ecdsa::Signature,
+)> {
+    let secp = Secp256k1::new();
+    let master_key = ExtendedPrivKey::new_master(bitcoin::Network::Bitcoin, entropy)?;
+
+    // m/138'/0
+    let hashing_key = master_key.derive_priv(
+        &secp,
+        &[
+            ChildNumber::from_hardened_idx(138)?,
+            ChildNumber::from_normal_idx(0)?,
+        ],
+    )?;
+
+    let mut path = vec![ChildNumber::from_hardened_idx(138)?];
+    path.extend(
+        lnurl_auth_path_indices(&hashing_key.private_key.secret_bytes(), domain)
+            .into_iter()
+            .map(ChildNumber::from),
+    );
+    let linking_key = master_key.derive_priv(&secp, &DerivationPath::from(path))?;
+
+    let signature = secp.sign_ecdsa(&Message::from_slice(&k1)?, &linking_key.private_key);
+    Ok((linking_key.private_key.public_key(&secp), signature))
+}
+
+/// Indices of the linking key path m/138'/<long1>/<long2>/<long3>/<long4>,
+/// taken from the first 16 bytes of the domain's HMAC
+fn lnurl_auth_path_indices(hashing_key: &[u8; 32], domain: &str) -> [u32; 4] {
+    let mut engine = HmacEngine::<sha256::Hash>::new(hashing_key);
+    engine.input(domain.as_bytes());
+    let domain_hash = Hmac::<sha256::Hash>::from_engine(engine).into_inner();
+
+    let mut indices = [0; 4];
+    for (index, chunk) in indices.iter_mut().zip(domain_hash.chunks(4)) {
+        *index = u32::from_be_bytes(chunk.try_into().expect("chunks have 4 bytes"));
+    }
+    indices
+}
+
+// TODO: use bip39 and proper derivation
+async fn load_or_generate_entropy(db: &Database) -> [u8; 64] {
+    if let Ok(entropy) = Client::load_decodable_client_secret::<[u8; 64]>(db).await {
+        entropy
+    } else {
+        info!("Generating mnemonic and writing entropy to client storage");
+        let entropy = PlainRootSecretStrategy::random(&mut thread_rng());
+        Client::store_encodable_client_secret(db, entropy)
+            .await
+            .expect("DB error");
+        entropy
+    }
+}
+
+async fn join_federation(
+    wallet_db: PersistentMemDb,
+    invite_code: &InviteCode,
+) -> anyhow::Result<ClientHandle> {
+    let mut client_builder = fedimint_client::Client::builder(wallet_db.into());
+
+    let client_secret = load_or_generate_entropy(client_builder.db()).await;
+
+    client_builder.with_module(WalletClientInit(None));
+    client_builder.with_module(MintClientInit);
+    client_builder.with_module(LightningClientInit);
+    client_builder.with_primary_module(1);
+    ClientConfig::download_from_invite_code(invite_code)
+        .and_then(|cfg| {
+            client_builder.join(PlainRootSecretStrategy::to_root_secret(&client_secret), cfg)
+        })
+        .await
+}
+
+/// Reads the entire operation log, newest operations first.
+async fn list_all_transactions(client: &Client) -> Vec<Transaction> {
+    const PAGE_SIZE: usize = 100;
+
+    let mut labels = load_operation_labels(client).await;
+    let mut transactions = Vec::new();
+    let mut last_key = None;
+    loop {
+        let page = client
+            .operation_log()
+            .list_operations(PAGE_SIZE, last_key)
+            .await;
+        let page_len = page.len();
+
+        last_key = None;
+        for (key, op_log) in page {
+            transactions.extend(transaction_from_op_log(
+                key.creation_time,
+                key.operation_id,
+                &op_log,
+            ));
+            last_key = Some(key);
+        }
+
+        if page_len < PAGE_SIZE {
+            break;
+        }
+    }
+
+    for transaction in &mut transactions {
+        if let Some(label) = labels.remove(&transaction.operation_id) {
+            transaction.set_label(label);
+        }
+    }
+
+    transactions
+}
+
+/// Reconstructs the balance over time from the operation log, oldest first
+fn balance_history(mut transactions: Vec<Transaction>) -> Vec<BalancePoint> {
+    TransactionSort::Oldest.sort(&mut transactions);
+
+    let mut balance_msat = 0;
+    transactions
+        .into_iter()
+        .filter_map(|transaction| {
+            let change_msat = transaction.balance_change_msat();
+            if change_msat == 0 {
+                return None;
+            }
+
+            balance_msat += change_msat;
+            Some(BalancePoint {
+                timestamp: transaction.timestamp,
+                balance_msat,
+            })
+        })
+        .collect()
+}
+
+async fn load_operation_labels(client: &Client) -> HashMap<OperationId, OperationLabel> {
+    let mut dbtx = client.db().begin_transaction_nc().await;
+    let labels = dbtx
+        .find_by_prefix(&OperationLabelKeyPrefix)
+        .await
+        .map(|(OperationLabelKey(operation_id), label)| (operation_id, label))
+        .collect()
+        .await;
+    labels
+}
+
+/// Loads the operation from the log, including the label attached to it
+async fn load_transaction(
+    client: &Client,
+    timestamp: SystemTime,
+    operation_id: OperationId,
+) -> Option<Transaction> {
+    let op_log = client.operation_log().get_operation(operation_id).await?;
+    let mut transaction = transaction_from_op_log(timestamp, operation_id, &op_log)?;
+
+    let label = client
+        .db()
+        .begin_transaction_nc()
+        .await
+        .get_value(&OperationLabelKey(operation_id))
+        .await;
+    if let Some(label) = label {
+        transaction.set_label(label);
+    }
+
+    Some(transaction)
+}
+
+fn transaction_from_op_log(
+    timestamp: SystemTime,
+    operation_id: OperationId,
+    op_log: &OperationLogEntry,
+) -> Option<Transaction> {
+    let mut fee_msat = 0;
+    let mut reclaim_deadline = None;
+    let (direction, amount_msat, status, description) = match op_log.operation_module_kind() {
+        "mint" => {
+            let meta = op_log.meta::<MintOperationMeta>();
+            match meta.variant {
+                MintOperationMetaVariant::Reissuance { .. } => {
+                    let status = match op_log.outcome::<ReissueExternalNotesState>() {
+                        Some(ReissueExternalNotesState::Done) => TransactionStatus::Success,
+                        Some(ReissueExternalNotesState::Failed(_)) => TransactionStatus::Failed,
+                        _ => TransactionStatus::Pending,
+                    };
+                    (
+                        TransactionDirection::Incoming,
+                        meta.amount.msats as i64,
+                        status,
+                        None,
+                    )
+                }
+                MintOperationMetaVariant::SpendOOB { .. } => {
+                    reclaim_deadline =
+                        serde_json::from_value::<EcashSpendExtraMeta>(meta.extra_meta.clone())
+                            .ok()
+                            .and_then(|meta| meta.reclaim_deadline);
+                    let status = match op_log.outcome::<SpendOOBState>() {
+                        Some(SpendOOBState::Success | SpendOOBState::UserCanceledFailure) => {
+                            TransactionStatus::Success
+                        }
+                        Some(SpendOOBState::Refunded | SpendOOBState::UserCanceledSuccess) => {
+                            TransactionStatus::Refunded
+                        }
+                        _ => TransactionStatus::Pending,
+                    };
+                    (
+                        TransactionDirection::Outgoing,
+                        -(meta.amount.msats as i64),
+                        status,
+                        None,
+                    )
+                }
+            }
+        }
+        "ln" => match op_log.meta::<LightningOperationMeta>().variant {
+            LightningOperationMetaVariant::Receive { invoice, .. } => {
+                let amount = invoice
+                    .amount_milli_satoshis()
+                    .expect("We don't create 0 amount invoices")
+                    as i64;
+                // Receives only end once the client gave up waiting, so unpaid
+                // invoices past their expiry are marked as expired right away
+                let expired = invoice_expires_at(&invoice) <= now();
+                let status = match op_log.outcome::<LnReceiveState>() {
+                    Some(LnReceiveState::Claimed) => TransactionStatus::Success,
+                    Some(LnReceiveState::Canceled { .. }) if expired => TransactionStatus::Expired,
+                    Some(LnReceiveState::Canceled { .. }) => TransactionStatus::Canceled,
+                    None if expired => TransactionStatus::Expired,
+                    _ => TransactionStatus::Pending,
+                };
+
+                let description = invoice_description(&invoice).or_else(|| {
+                    serde_json::from_value::<LnReceiveExtraMeta>(
+                        op_log.meta::<LightningOperationMeta>().extra_meta,
+                    )
+                    .ok()
+                    .and_then(|meta| meta.description)
+                });
+
+                (TransactionDirection::Incoming, amount, status, description)
+            }
+            LightningOperationMetaVariant::Pay(LightningOperationMetaPay {
+                invoice,
+                fee,
+                is_internal_payment,
+                ..
+            }) => {
+                fee_msat = fee.msats;
+                let amount = -(invoice
+                    .amount_milli_satoshis()
+                    .expect("Can't pay 0 amount invoices") as i64);
+                let status = if is_internal_payment {
+                    match op_log.outcome::<InternalPayState>() {
+                        Some(InternalPayState::Preimage(_)) => TransactionStatus::Success,
+                        Some(InternalPayState::RefundSuccess { .. }) => TransactionStatus::Refunded,
+                        Some(
+                            InternalPayState::RefundError { .. }
+                            | InternalPayState::FundingFailed { .. }
+                            | InternalPayState::UnexpectedError(_),
+                        ) => TransactionStatus::Failed,
+                        _ => TransactionStatus::Pending,
+                    }
+                } else {
+                    match op_log.outcome::<LnPayState>() {
+                        Some(LnPayState::Success { .. }) => TransactionStatus::Success,
+                        Some(LnPayState::Refunded { .. }) => TransactionStatus::Refunded,
+                        Some(LnPayState::Canceled | LnPayState::UnexpectedError { .. }) => {
+                            TransactionStatus::Failed
+                        }
+                        _ => TransactionStatus::Pending,
+                    }
+                };
+
+                (
+                    TransactionDirection::Outgoing,
+                    amount,
+                    status,
+                    invoice_description(&invoice),
+                )
+            }
+            LightningOperationMetaVariant::Claim { .. } => {
+                debug!("Skipping LN claim operation {operation_id:?}, we never create these");
+                return None;
+            }
+        },
+        kind => {
+            debug!("Skipping operation {operation_id:?} of unsupported module {kind}");
+            return None;
+        }
+    };
+
+    Some(Transaction {
+        timestamp,
+        operation_id,
+        operation_kind: op_log.operation_module_kind().to_owned(),
+        direction,
+        amount_msat,
+        fee_msat,
+        status,
+        description,
+        label: None,
+        tags: vec![],
+        reclaim_deadline,
+    })
+}
+
+async fn vetted_gateways(client: &Client) -> Vec<PublicKey> {
+    client
+        .meta_service()
+        .get_field::<Vec<PublicKey>>(client.db(), "vetted_gateways")
+        .await
+        .and_then(|meta_field| meta_field.value)
+        .unwrap_or_default()
+}
+
+async fn list_gateway_infos(client: &Client) -> GatewayList {
+    let vetted_gateways = vetted_gateways(client).await;
+
+    let mut dbtx = client.db().begin_transaction_nc().await;
+    let vetting_policy = dbtx
+        .get_value(&GatewayVettingPolicyKey)
+        .await
+        .unwrap_or_default();
+    let preferred_gateway = dbtx.get_value(&PreferredGatewayKey).await;
+    let last_seen = dbtx
+        .find_by_prefix(&GatewayLastSeenKeyPrefix)
+        .await
+        .map(|(GatewayLastSeenKey(gateway_id), last_seen)| (gateway_id, last_seen))
+        .collect::<HashMap<_, _>>()
+        .await;
+
+    let gateways = client
+        .get_first_module::<LightningClientModule>()
+        .list_gateways()
+        .await
+        .into_iter()
+        .map(|gw| {
+            let vetted = vetted_gateways.contains(&gw.info.gateway_id);
+            GatewayInfo {
+                gateway_id: gw.info.gateway_id,
+                node_pub_key: gw.info.node_pub_key,
+                lightning_alias: gw.info.lightning_alias.clone(),
+                fee_base_msat: gw.info.fees.base_msat,
+                fee_proportional_millionths: gw.info.fees.proportional_millionths,
+                vetted,
+                usable: vetting_policy.allows(vetted),
+                last_seen: last_seen.get(&gw.info.gateway_id).copied(),
+                preferred: preferred_gateway == Some(gw.info.gateway_id),
+            }
+        })
+        .collect();
+
+    GatewayList {
+        gateways,
+        vetting_policy,
+    }
+}
+
+/// Picks the gateway to use for a payment among the ones the wallet's vetting
+/// policy allows. A gateway explicitly requested for the payment has to be
+/// usable, if the pinned one isn't we fall back to any usable gateway.
+/// Returns `None` if there is no usable gateway at all.
+async fn select_gateway(
+    client: &Client,
+    gateway_id: Option<PublicKey>,
+) -> anyhow::Result<Option<LightningGateway>> {
+    let vetted_gateways = vetted_gateways(client).await;
+    let (vetting_policy, preferred_gateway) = {
+        let mut dbtx = client.db().begin_transaction_nc().await;
+        (
+            dbtx.get_value(&GatewayVettingPolicyKey)
+                .await
+                .unwrap_or_default(),
+            dbtx.get_value(&PreferredGatewayKey).await,
+        )
+    };
+
+    let gateways = client
+        .get_first_module::<LightningClientModule>()
+        .list_gateways()
+        .await
+        .into_iter()
+        .map(|gw| (vetted_gateways.contains(&gw.info.gateway_id), gw.info))
+        .filter(|(vetted, _)| vetting_policy.allows(*vetted))
+        .collect::<Vec<_>>();
+    let find_gateway = |gateway_id: PublicKey| {
+        gateways
+            .iter()
+            .find(|(_, gw)| gw.gateway_id == gateway_id)
+            .map(|(_, gw)| gw.clone())
+    };
+
+    if let Some(gateway_id) = gateway_id {
+        return find_gateway(gateway_id).map(Some).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Selected gateway {gateway_id} is not available or not allowed by the wallet's vetting policy"
+            )
+        });
+    }
+
+    if let Some(gateway) = preferred_gateway.and_then(find_gateway) {
+        return Ok(Some(gateway));
+    }
+
+    Ok(gateways
+        .iter()
+        .find(|(vetted, _)| *vetted && vetting_policy.prefers_vetted())
+        .or_else(|| gateways.first())
+        .map(|(_, gw)| gw.clone()))
+}
+
+fn no_usable_gateway_error() -> anyhow::Error {
+    anyhow::anyhow!(
+        "No usable Lightning gateway: the federation doesn't list any gateway allowed by this \
+         wallet's gateway vetting policy"
+    )
+}
+
+/// Subscribers to the state of pending operations. Every operation is watched
+/// by a single task that forwards its updates to all subscribers and ends once
+/// the operation finished or nobody listens anymore.
+#[derive(Default)]
+struct TransactionWatchers(Mutex<HashMap<OperationId, Vec<mpsc::UnboundedSender<Transaction>>>>);
+
+impl TransactionWatchers {
+    fn subscribe(
+        &'static self,
+        client: &'static Client,
+        transaction: &Transaction,
+        sender: mpsc::UnboundedSender<Transaction>,
+    ) {
+        let operation_id = transaction.operation_id;
+        {
+            let mut watchers = self.0.lock().expect("poisoned");
+            if let Some(senders) = watchers.get_mut(&operation_id) {
+                senders.push(sender);
+                return;
+            }
+            watchers.insert(operation_id, vec![sender]);
+        }
+        spawn_transaction_watcher(client, self, transaction);
+    }
+
+    /// Sends `transaction` to all subscribers of its operation and returns
+    /// whether anyone is still listening. If `finished` the subscribers are
+    /// removed, so later subscriptions start a new watcher.
+    fn publish(&self, transaction: Transaction, finished: bool) -> bool {
+        let mut watchers = self.0.lock().expect("poisoned");
+        let Some(senders) = watchers.get_mut(&transaction.operation_id) else {
+            return false;
+        };
+        senders.retain(|sender| sender.send(transaction.clone()).is_ok());
+        if finished || senders.is_empty() {
+            watchers.remove(&transaction.operation_id);
+            return false;
+        }
+        true
+    }
+
+    fn unsubscribe_all(&self, operation_id: OperationId) {
+        self.0.lock().expect("poisoned").remove(&operation_id);
+    }
+}
+
+/// Streams a [`Transaction`] every time an operation is added to the log or
+/// the state of a pending operation changes.
+async fn watch_transactions(
+    client: &'static Client,
+    watchers: &'static TransactionWatchers,
+    sender: mpsc::UnboundedSender<Transaction>,
+) {
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+    const POLL_LIMIT: usize = 20;
+
+    let mut known_operations = HashSet::new();
+    for transaction in list_all_transactions(client).await {
+        known_operations.insert(transaction.operation_id);
+        if transaction.status == TransactionStatus::Pending {
+            watchers.subscribe(client, &transaction, sender.clone());
+        }
+    }
+
+    // The operation log can't be subscribed to, so look for new operations
+    // periodically. Only the newest entries need to be checked since
+    // operations are never inserted in the past.
+    while !sender.is_closed() {
+        for (key, op_log) in client
+            .operation_log()
+            .list_operations(POLL_LIMIT, None)
+            .await
+        {
+            if !known_operations.insert(key.operation_id) {
+                continue;
+            }
+
+            let Some(transaction) =
+                transaction_from_op_log(key.creation_time, key.operation_id, &op_log)
+            else {
+                continue;
+            };
+
+            if transaction.status == TransactionStatus::Pending {
+                watchers.subscribe(client, &transaction, sender.clone());
+            }
+            if sender.send(transaction).is_err() {
+                return;
+            }
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+fn spawn_transaction_watcher(
+    client: &'static Client,
+    watchers: &'static TransactionWatchers,
+    transaction: &Transaction,
+) {
+    let timestamp = transaction.timestamp;
+    let operation_id = transaction.operation_id;
+    spawn("transaction state watcher", async move {
+        let mut updates = match operation_updates(client, operation_id).await {
+            Ok(updates) => updates,
+            Err(e) => {
+                warn!("Could not subscribe to operation {operation_id:?}: {e:?}");
+                watchers.unsubscribe_all(operation_id);
+                return;
+            }
+        };
+
+        // The final status is only written to the operation log once the update
+        // stream ended, so the transaction is sent once more afterwards
+        loop {
+            let finished = updates.next().await.is_none();
+
+            let Some(transaction) = load_transaction(client, timestamp, operation_id).await else {
+                watchers.unsubscribe_all(operation_id);
+                return;
+            };
+            if !watchers.publish(transaction, finished) {
+                return;
+            }
+        }
+    });
+}
+
+async fn ecash_reclaim_timeout(client: &Client) -> Duration {
+    client
+        .db()
+        .begin_transaction_nc()
+        .await
+        .get_value(&EcashReclaimTimeoutKey)
+        .await
+        .map_or(DEFAULT_ECASH_RECLAIM_TIMEOUT, Duration::from_secs)
+}
+
+async fn note_holdings(client: &Client) -> Vec<(Amount, usize)> {
+    let mint = client.get_first_module::<MintClientModule>();
+    let mut dbtx = mint.db.begin_transaction_nc().await;
+    mint.get_wallet_summary(&mut dbtx).await.iter().collect()
+}
+
+async fn note_count(client: &Client) -> usize {
+    note_holdings(client)
+        .await
+        .into_iter()
+        .map(|(_, count)| count)
+        .sum()
+}
+
+async fn receive_ecash(client: &Client, notes: &str) -> anyhow::Result<RpcResponse> {
+    let notes = notes.trim();
+    info!("Receiving notes: \"{notes}\"");
+    let notes: OOBNotes = notes.parse()?;
+    let amount = notes.total_amount();
+    let mint = client.get_first_module::<MintClientModule>();
+    let operation_id = mint.reissue_external_notes(notes, ()).await?;
+    let updates = mint
+        .subscribe_reissue_external_notes(operation_id)
+        .await?
+        .into_stream()
+        .map(EcashReceiveProgress::from);
+    Ok(RpcResponse::EcashReceive {
+        operation_id,
+        amount,
+        updates: Box::pin(updates),
+    })
+}
+
+fn preview_ecash(client: &Client, notes: &str) -> anyhow::Result<EcashPreview> {
+    let notes = notes.trim();
+    let oob_notes: OOBNotes = notes.parse()?;
+    let federation_id_prefix = oob_notes.federation_id_prefix();
+
+    Ok(EcashPreview {
+        notes: notes.to_owned(),
+        total_amount: oob_notes.total_amount(),
+        denominations: oob_notes.notes().summary().iter().collect(),
+        federation_id_prefix: federation_id_prefix.to_string(),
+        same_federation: client.federation_id().to_prefix() == federation_id_prefix,
+        federation_invite: oob_notes
+            .federation_invite()
+            .map(|invite| invite.to_string()),
+    })
+}
+
+/// Spends notes held locally, which doesn't need the federation to be
+/// reachable
+async fn spend_notes(
+    client: &Client,
+    amount: Amount,
+    reclaim_after: Duration,
+    include_invite: bool,
+) -> anyhow::Result<(OperationId, OOBNotes)> {
+    let extra_meta = EcashSpendExtraMeta {
+        reclaim_deadline: Some(now() + reclaim_after),
+    };
+    client
+        .get_first_module::<MintClientModule>()
+        .spend_notes(amount, reclaim_after, include_invite, extra_meta)
+        .await
+}
+
+/// Spends notes worth exactly `amount`. If the wallet lacks the right
+/// denominations the selected notes are taken back right away, which makes the
+/// federation reissue them into smaller denominations, and the spend is
+/// retried.
+async fn spend_exact_notes(
+    client: &Client,
+    amount: Amount,
+    reclaim_after: Duration,
+    include_invite: bool,
+) -> anyhow::Result<OOBNotes> {
+    const MAX_EXCHANGES: usize = 3;
+
+    for _ in 0..MAX_EXCHANGES {
+        let (operation_id, notes) =
+            spend_notes(client, amount, reclaim_after, include_invite).await?;
+        if notes.total_amount() == amount {
+            return Ok(notes);
+        }
+
+        exchange_notes(client, operation_id, "Exchanged for exact denominations").await?;
+    }
+
+    anyhow::bail!("The federation's denominations can't represent {amount} exactly")
+}
+
+/// Takes back the notes of our own spend `operation_id` right away, which makes
+/// the federation reissue them into the denominations the wallet lacks. Returns
+/// once the reissued notes are in the wallet. The spend is labelled with
+/// `label` so it can be told apart from real ones.
+async fn exchange_notes(
+    client: &Client,
+    operation_id: OperationId,
+    label: &str,
+) -> anyhow::Result<()> {
+    const EXCHANGE_TIMEOUT: Duration = Duration::from_secs(30);
 
-#[derive(Debug, Clone)]
-enum RpcRequest {
-    ListWallets,
-    SelectWallet(String),
-    Join(String),
-    GetName,
-    SubscribeBalance,
-    EcashSend(Amount),
-    EcashReceive(String),
-    LnSend(String),
-    LnReceive { amount: Amount, description: String },
-    // TODO: pagination
-    ListTransactions,
+    let mut dbtx = client.db().begin_transaction().await;
+    dbtx.insert_entry(
+        &OperationLabelKey(operation_id),
+        &OperationLabel {
+            label: label.to_owned(),
+            tags: vec![],
+        },
+    )
+    .await;
+    dbtx.commit_tx().await;
+
+    let exchange = async {
+        let reclaimed = reclaim_ecash(client, operation_id).await?;
+        if reclaimed {
+            await_reissued_notes(client, operation_id).await;
+        }
+        anyhow::Ok(reclaimed)
+    };
+    match timeout(EXCHANGE_TIMEOUT, exchange).await {
+        Ok(Ok(true)) => Ok(()),
+        Ok(Ok(false)) => anyhow::bail!("The exchanged notes were redeemed by someone else"),
+        Ok(Err(e)) => Err(e),
+        // The notes are reissued once the federation is reachable again
+        Err(_) => Err(FederationUnreachable.into()),
+    }
 }
 
-enum RpcResponse {
-    ListWallets(Vec<String>),
-    SelectWallet {
-        initialized: bool,
-    },
-    Join,
-    GetName(String),
-    SubscribeBalance(BoxStream<'static, Amount>),
-    EcashSend(OOBNotes),
-    EcashReceive(Amount),
-    LnSend,
-    LnReceive {
-        invoice: String,
-        await_paid: watch::Receiver<bool>,
-    },
-    ListTransactions(Vec<Transaction>),
+/// The federation accepting the refund of a canceled spend doesn't mean the
+/// reissued notes were issued yet. Waits until the operation's outputs are
+/// final, which adds the notes to the balance.
+async fn await_reissued_notes(client: &Client, operation_id: OperationId) {
+    let mut balance_changes = client.subscribe_balance_changes().await;
+    while client.has_active_states(operation_id).await {
+        if balance_changes.next().await.is_none() {
+            break;
+        }
+    }
 }
 
-// TODO: add status update stream
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Transaction {
-    pub timestamp: SystemTime,
-    pub operation_id: OperationId,
-    pub operation_kind: String,
-    pub amount_msat: i64,
-    pub description: Option<String>,
+/// Reissues all notes of the wallet at once, which merges the many small notes
+/// wallets accumulate. `consolidating` is set while it runs, only one
+/// consolidation can run at a time.
+///
+/// The mint client picks the denominations of the new notes, keeping a few of
+/// each so that every amount can still be represented. fedimint 0.3 doesn't
+/// let the caller choose a different target distribution.
+async fn consolidate_notes(
+    client: &Client,
+    consolidating: &watch::Sender<bool>,
+) -> anyhow::Result<NoteConsolidation> {
+    if !consolidating.send_if_modified(|running| !std::mem::replace(running, true)) {
+        anyhow::bail!("The notes are already being consolidated");
+    }
+    let result = consolidate_all_notes(client).await;
+    consolidating.send_replace(false);
+    result
 }
 
-impl Debug for RpcResponse {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "RpcResponse::?")
+async fn consolidate_all_notes(client: &Client) -> anyhow::Result<NoteConsolidation> {
+    let notes_before = note_count(client).await;
+    let balance = client.get_balance().await;
+    if balance == Amount::ZERO {
+        return Ok(NoteConsolidation {
+            notes_before,
+            notes_after: notes_before,
+        });
     }
+
+    let (operation_id, _) =
+        spend_notes(client, balance, DEFAULT_ECASH_RECLAIM_TIMEOUT, false).await?;
+    // Only returns once the reissued notes are in the wallet, so they are
+    // counted below
+    exchange_notes(client, operation_id, "Consolidated notes").await?;
+
+    Ok(NoteConsolidation {
+        notes_before,
+        notes_after: note_count(client).await,
+    })
 }
 
-#[derive(Debug, ThisError, Serialize, Deserialize, Clone)]
-pub enum RpcError {
-    #[error("Invalid response")]
-    InvalidResponse,
-    #[error("Client stopped")]
-    ClientStopped(String),
+/// Consolidates the wallet's notes whenever it holds more than the configured
+/// number of notes and no payment is in flight, since consolidating spends the
+/// entire balance until the federation reissued the notes
+async fn consolidate_notes_automatically(client: &Client, consolidating: &watch::Sender<bool>) {
+    // If the federation's denominations can't get the wallet below the
+    // threshold, only try again once more notes accumulated
+    let mut last_note_count = 0;
+
+    let mut balance_changes = client.subscribe_balance_changes().await;
+    while balance_changes.next().await.is_some() {
+        let Some(threshold) = client
+            .db()
+            .begin_transaction_nc()
+            .await
+            .get_value(&NoteConsolidationThresholdKey)
+            .await
+        else {
+            continue;
+        };
+
+        let notes = note_count(client).await;
+        if notes as u64 <= threshold {
+            // Spending got the wallet below the threshold, so consolidating
+            // can help again
+            last_note_count = 0;
+            continue;
+        }
+        if notes <= last_note_count {
+            continue;
+        }
+        if has_pending_payments(client).await {
+            debug!("Postponing note consolidation until pending payments finished");
+            continue;
+        }
+
+        match consolidate_notes(client, consolidating).await {
+            Ok(consolidation) => {
+                info!("Merged {} notes", consolidation.merged());
+                last_note_count = consolidation.notes_after;
+            }
+            Err(e) => warn!("Failed to consolidate notes: {e:?}"),
+        }
+    }
 }
 
-impl From<anyhow::Error> for RpcError {
-    fn from(e: anyhow::Error) -> Self {
-        Self::ClientStopped(e.to_string())
+/// Whether any operation besides ecash waiting to be redeemed by its recipient
+/// is still in flight
+async fn has_pending_payments(client: &Client) -> bool {
+    list_all_transactions(client)
+        .await
+        .iter()
+        .any(|transaction| {
+            let awaiting_redemption = transaction.operation_kind == "mint"
+                && transaction.direction == TransactionDirection::Outgoing;
+            transaction.status == TransactionStatus::Pending && !awaiting_redemption
+        })
+}
+
+/// Cancels an out-of-band ecash spend and waits until the federation decided
+/// whether the notes can be reissued into our wallet or were already redeemed
+/// by the recipient.
+async fn reclaim_ecash(client: &Client, operation_id: OperationId) -> anyhow::Result<bool> {
+    let mint = client.get_first_module::<MintClientModule>();
+    let mut updates = mint
+        .subscribe_spend_notes(operation_id)
+        .await?
+        .into_stream();
+    mint.try_cancel_spend_notes(operation_id).await;
+
+    while let Some(state) = updates.next().await {
+        match state {
+            SpendOOBState::UserCanceledSuccess | SpendOOBState::Refunded => return Ok(true),
+            SpendOOBState::UserCanceledFailure | SpendOOBState::Success => return Ok(false),
+            _ => {}
+        }
     }
+    anyhow::bail!("The spend ended in an unexpected state")
 }
 
-type RpcCall = (RpcRequest, oneshot::Sender<anyhow::Result<RpcResponse>>);
+async fn ln_payment_updates(
+    client: &Client,
+    payment_type: PayType,
+) -> anyhow::Result<(OperationId, BoxStream<'static, LnPaymentState>)> {
+    let ln = client.get_first_module::<LightningClientModule>();
+    Ok(match payment_type {
+        PayType::Internal(operation_id) => (
+            operation_id,
+            Box::pin(
+                ln.subscribe_internal_pay(operation_id)
+                    .await?
+                    .into_stream()
+                    .map(LnPaymentState::from),
+            ),
+        ),
+        PayType::Lightning(operation_id) => (
+            operation_id,
+            Box::pin(
+                ln.subscribe_ln_pay(operation_id)
+                    .await?
+                    .into_stream()
+                    .map(LnPaymentState::from),
+            ),
+        ),
+    })
+}
 
-// TODO: use bip39 and proper derivation
-async fn load_or_generate_entropy(db: &Database) -> [u8; 64] {
-    if let Ok(entropy) = Client::load_decodable_client_secret::<[u8; 64]>(db).await {
-        entropy
-    } else {
-        info!("Generating mnemonic and writing entropy to client storage");
-        let entropy = PlainRootSecretStrategy::random(&mut thread_rng());
-        Client::store_encodable_client_secret(db, entropy)
-            .await
-            .expect("DB error");
-        entropy
+/// Yields every time the operation's state machine makes progress and ends
+/// once it reached a final state.
+async fn operation_updates(
+    client: &Client,
+    operation_id: OperationId,
+) -> anyhow::Result<BoxStream<'static, ()>> {
+    let op_log = client
+        .operation_log()
+        .get_operation(operation_id)
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Operation not found"))?;
+
+    let updates: BoxStream<'static, ()> = match op_log.operation_module_kind() {
+        "mint" => {
+            let mint = client.get_first_module::<MintClientModule>();
+            match op_log.meta::<MintOperationMeta>().variant {
+                MintOperationMetaVariant::Reissuance { .. } => Box::pin(
+                    mint.subscribe_reissue_external_notes(operation_id)
+                        .await?
+                        .into_stream()
+                        .map(|_| ()),
+                ),
+                MintOperationMetaVariant::SpendOOB { .. } => Box::pin(
+                    mint.subscribe_spend_notes(operation_id)
+                        .await?
+                        .into_stream()
+                        .map(|_| ()),
+                ),
+            }
+        }
+        "ln" => {
+            let ln = client.get_first_module::<LightningClientModule>();
+            match op_log.meta::<LightningOperationMeta>().variant {
+                LightningOperationMetaVariant::Receive { .. } => Box::pin(
+                    ln.subscribe_ln_receive(operation_id)
+                        .await?
+                        .into_stream()
+                        .map(|_| ()),
+                ),
+                LightningOperationMetaVariant::Pay(LightningOperationMetaPay {
+                    is_internal_payment: true,
+                    ..
+                }) => Box::pin(
+                    ln.subscribe_internal_pay(operation_id)
+                        .await?
+                        .into_stream()
+                        .map(|_| ()),
+                ),
+                LightningOperationMetaVariant::Pay(_) => Box::pin(
+                    ln.subscribe_ln_pay(operation_id)
+                        .await?
+                        .into_stream()
+                        .map(|_| ()),
+                ),
+                LightningOperationMetaVariant::Claim { .. } => {
+                    anyhow::bail!("LN claim operations are not supported")
+                }
+            }
+        }
+        kind => anyhow::bail!("Operations of module {kind} are not supported"),
+    };
+
+    Ok(updates)
+}
+
+/// Rejects invoices that can't be paid by this wallet anyway, before
+/// anything is sent to the federation
+fn validate_invoice(client: &Client, invoice: &Bolt11Invoice) -> anyhow::Result<()> {
+    let network = client
+        .get_first_module::<WalletClientModule>()
+        .get_network();
+    if invoice.currency() != Currency::from(network) {
+        anyhow::bail!(
+            "The invoice is for {:?}, but the federation uses {network}",
+            invoice.currency()
+        );
+    }
+
+    if invoice_expires_at(invoice) <= now() {
+        anyhow::bail!("The invoice has expired");
+    }
+
+    Ok(())
+}
+
+/// Uses the invoice's own timestamp rather than `Bolt11Invoice::is_expired`,
+/// which relies on `SystemTime::now` that isn't available in the browser.
+fn invoice_expires_at(invoice: &Bolt11Invoice) -> SystemTime {
+    SystemTime::UNIX_EPOCH + invoice.duration_since_epoch() + invoice.expiry_time()
+}
+
+async fn preview_invoice(
+    client: &Client,
+    invoice: &str,
+    gateway_id: Option<PublicKey>,
+) -> anyhow::Result<InvoicePreview> {
+    let invoice =
+        Bolt11Invoice::from_str(invoice).map_err(|e| anyhow::anyhow!("Invalid invoice: {e:?}"))?;
+    validate_invoice(client, &invoice)?;
+
+    let gateway = select_gateway(client, gateway_id).await?;
+    let amount_msat = invoice.amount_milli_satoshis();
+    let description_hash = match invoice.description() {
+        Bolt11InvoiceDescription::Direct(_) => None,
+        Bolt11InvoiceDescription::Hash(hash) => Some(hash.0.to_string()),
+    };
+
+    Ok(InvoicePreview {
+        invoice: invoice.to_string(),
+        amount_msat,
+        description: invoice_description(&invoice),
+        description_hash,
+        payee: invoice.recover_payee_pub_key(),
+        expires_at: invoice_expires_at(&invoice),
+        gateway_alias: gateway.as_ref().map(|gw| gw.lightning_alias.clone()),
+        estimated_fee_msat: gateway.as_ref().map_or(0, |gw| {
+            gateway_fee_msat(&gw.fees, amount_msat.unwrap_or_default())
+        }),
+        balance_msat: client.get_balance().await.msats,
+    })
+}
+
+fn invoice_description(invoice: &Bolt11Invoice) -> Option<String> {
+    match invoice.description() {
+        Bolt11InvoiceDescription::Direct(description) => Some(description.to_string()),
+        Bolt11InvoiceDescription::Hash(_) => None,
     }
 }
 
@@ -179,22 +1783,7 @@ async fn run_client(mut rpc: mpsc::Receiver<RpcCall>) {
                 }
             };
 
-            let mut client_builder = fedimint_client::Client::builder(wallet_db.clone().into());
-
-            let client_secret = load_or_generate_entropy(client_builder.db()).await;
-
-            client_builder.with_module(WalletClientInit(None));
-            client_builder.with_module(MintClientInit);
-            client_builder.with_module(LightningClientInit);
-            client_builder.with_primary_module(1);
-            let client_res = ClientConfig::download_from_invite_code(&invite_code)
-                .and_then(|cfg| {
-                    client_builder
-                        .join(PlainRootSecretStrategy::to_root_secret(&client_secret), cfg)
-                })
-                .await;
-
-            match client_res {
+            match join_federation(wallet_db.clone(), &invite_code).await {
                 Ok(client) => {
                     let _ = response_sender
                         .send(Ok(RpcResponse::Join))
@@ -202,6 +1791,9 @@ async fn run_client(mut rpc: mpsc::Receiver<RpcCall>) {
                     break client;
                 }
                 Err(e) => {
+                    // Only the generated secret was stored, don't leave the
+                    // wallet behind half-created
+                    wallet_db.delete();
                     let _ = response_sender
                         .send(Err(anyhow::anyhow!("Failed to initialize client: {e:?}")))
                         .map_err(|_| warn!("RPC receiver dropped before response was sent"));
@@ -229,30 +1821,36 @@ async fn run_client(mut rpc: mpsc::Receiver<RpcCall>) {
 
     info!("Client initialized");
 
-    let meta_service = client.meta_service().clone();
-    let db = client.db().clone();
-    spawn("gateway updater", async {
+    spawn("gateway updater", async move {
         client
             .get_first_module::<LightningClientModule>()
-            .update_gateway_cache_continuously(move |gws| {
-                let db_inner = db.clone();
-                let meta_service_inner = meta_service.clone();
-                async move {
-                    let vetted_gateways = meta_service_inner
-                        .get_field::<Vec<secp256k1_zkp::PublicKey>>(&db_inner, "vetted_gateways")
-                        .await
-                        .and_then(|meta_field| meta_field.value)
-                        .unwrap_or_default();
-                    gws.into_iter()
-                        .filter(|gw| vetted_gateways.contains(&gw.info.gateway_id))
-                        .collect()
+            .update_gateway_cache_continuously(move |gws| async move {
+                let seen_at = now();
+                let mut dbtx = client.db().begin_transaction().await;
+                for gw in &gws {
+                    dbtx.insert_entry(&GatewayLastSeenKey(gw.info.gateway_id), &seen_at)
+                        .await;
                 }
+                dbtx.commit_tx().await;
+
+                // Keep all gateways, which ones are usable depends on the
+                // wallet's vetting policy and is decided when selecting one
+                gws
             })
             .await
     });
 
     info!("Started gateway update service");
 
+    let consolidating: &watch::Sender<bool> = Box::leak(Box::new(watch::channel(false).0));
+    spawn(
+        "note consolidation",
+        consolidate_notes_automatically(client, consolidating),
+    );
+
+    let transaction_watchers: &TransactionWatchers =
+        Box::leak(Box::new(TransactionWatchers::default()));
+
     while let Some((rpc_request, response_sender)) = rpc.recv().await {
         debug!("Received RPC request: {:?}", rpc_request);
         match rpc_request {
@@ -270,85 +1868,227 @@ async fn run_client(mut rpc: mpsc::Receiver<RpcCall>) {
                     .send(Ok(RpcResponse::SubscribeBalance(stream)))
                     .map_err(|_| warn!("RPC receiver dropped before response was sent"));
             }
-            RpcRequest::EcashSend(amount) => {
-                const TRY_CANCEL_AFTER: Duration = Duration::from_secs(60 * 60 * 24 * 3); // 3 days
+            RpcRequest::EcashSend {
+                amount,
+                reclaim_after,
+                exact,
+                include_invite,
+            } => {
+                let reclaim_after = match reclaim_after {
+                    Some(reclaim_after) => reclaim_after,
+                    None => ecash_reclaim_timeout(client).await,
+                };
+                // Spending the whole balance selects every note, so it's always
+                // exact
+                let (amount, exact) = match amount {
+                    Some(amount) => (amount, exact),
+                    None => (client.get_balance().await, false),
+                };
 
-                let response = client
-                    .get_first_module::<MintClientModule>()
-                    .spend_notes(amount, TRY_CANCEL_AFTER, false, ())
-                    .await
-                    .map(|(_, notes)| RpcResponse::EcashSend(notes));
+                if exact {
+                    // Exchanging notes waits for the federation, so don't hold
+                    // up other requests
+                    spawn("exact ecash spend", async move {
+                        let _ = response_sender
+                            .send(
+                                spend_exact_notes(client, amount, reclaim_after, include_invite)
+                                    .await
+                                    .map(RpcResponse::EcashSend),
+                            )
+                            .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                    });
+                    continue;
+                }
 
                 let _ = response_sender
-                    .send(response)
+                    .send(
+                        spend_notes(client, amount, reclaim_after, include_invite)
+                            .await
+                            .map(|(_, notes)| RpcResponse::EcashSend(notes)),
+                    )
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::GetEcashReclaimTimeout => {
+                let _ = response_sender
+                    .send(Ok(RpcResponse::GetEcashReclaimTimeout(
+                        ecash_reclaim_timeout(client).await,
+                    )))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::SetEcashReclaimTimeout(timeout) => {
+                let mut dbtx = client.db().begin_transaction().await;
+                dbtx.insert_entry(&EcashReclaimTimeoutKey, &timeout.as_secs())
+                    .await;
+                dbtx.commit_tx().await;
+
+                let _ = response_sender
+                    .send(Ok(RpcResponse::SetEcashReclaimTimeout))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::ReclaimEcash(operation_id) => {
+                // Waits for the federation, so don't hold up other requests
+                spawn("ecash reclaim", async move {
+                    let _ = response_sender
+                        .send(
+                            reclaim_ecash(client, operation_id)
+                                .await
+                                .map(RpcResponse::ReclaimEcash),
+                        )
+                        .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                });
+            }
+            RpcRequest::PreviewEcash(notes) => {
+                let _ = response_sender
+                    .send(preview_ecash(client, &notes).map(RpcResponse::PreviewEcash))
                     .map_err(|_| warn!("RPC receiver dropped before response was sent"));
             }
             RpcRequest::EcashReceive(notes) => {
-                async fn receive_inner(
-                    client: &Client,
-                    notes: &str,
-                ) -> anyhow::Result<RpcResponse> {
-                    let notes = notes.trim();
-                    info!("Receiving notes: \"{notes}\"");
-                    let notes: OOBNotes = notes.parse()?;
-                    let amount = notes.total_amount();
-                    client
-                        .get_first_module::<MintClientModule>()
-                        .reissue_external_notes(notes, ())
-                        .await?;
-                    Ok(RpcResponse::EcashReceive(amount))
-                }
                 let _ = response_sender
-                    .send(receive_inner(client, &notes).await)
+                    .send(receive_ecash(client, &notes).await)
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::PreviewInvoice {
+                invoice,
+                gateway_id,
+            } => {
+                let _ = response_sender
+                    .send(
+                        preview_invoice(client, &invoice, gateway_id)
+                            .await
+                            .map(RpcResponse::PreviewInvoice),
+                    )
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::MaxPayable { gateway_id } => {
+                let response = match select_gateway(client, gateway_id).await {
+                    Ok(gateway) => {
+                        let balance_msat = client.get_balance().await.msats;
+                        // Without a gateway only internal payments are possible,
+                        // which don't have a fee
+                        Ok(RpcResponse::MaxPayable(match gateway {
+                            Some(gw) => max_payable_msat(balance_msat, &gw.fees),
+                            None => balance_msat,
+                        }))
+                    }
+                    Err(e) => Err(e),
+                };
+                let _ = response_sender
+                    .send(response)
                     .map_err(|_| warn!("RPC receiver dropped before response was sent"));
             }
-            RpcRequest::LnSend(invoice) => {
-                let invoice = match Bolt11Invoice::from_str(&invoice) {
+            RpcRequest::LnSend {
+                invoice,
+                gateway_id,
+            } => {
+                let invoice = match Bolt11Invoice::from_str(&invoice)
+                    .map_err(|e| anyhow::anyhow!("Invalid invoice: {e:?}"))
+                    .and_then(|invoice| validate_invoice(client, &invoice).map(|()| invoice))
+                {
                     Ok(invoice) => invoice,
                     Err(e) => {
                         let _ = response_sender
-                            .send(Err(anyhow::anyhow!("Invalid invoice: {e:?}")))
+                            .send(Err(e))
                             .map_err(|_| warn!("RPC receiver dropped before response was sent"));
                         continue;
                     }
                 };
 
-                let gateway = client
+                let gateway = match select_gateway(client, gateway_id).await {
+                    Ok(gateway) => gateway,
+                    Err(e) => {
+                        let _ = response_sender
+                            .send(Err(e))
+                            .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                        continue;
+                    }
+                };
+                // Without a gateway only invoices of users of the same federation
+                // can be paid, so still try but explain the failure
+                let no_gateway = gateway.is_none();
+                let payment = match client
                     .get_first_module::<LightningClientModule>()
-                    .list_gateways()
+                    .pay_bolt11_invoice(gateway, invoice, ())
                     .await
-                    .first()
-                    .map(|gw| gw.info.clone());
+                {
+                    Ok(payment) => payment,
+                    Err(e) => {
+                        let e = if no_gateway {
+                            no_usable_gateway_error().context(e)
+                        } else {
+                            e
+                        };
+                        let _ = response_sender
+                            .send(Err(e))
+                            .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                        continue;
+                    }
+                };
+
                 let _ = response_sender
-                    .send(
-                        client
-                            .get_first_module::<LightningClientModule>()
-                            .pay_bolt11_invoice(gateway, invoice, ())
-                            .await
-                            .map(|_| RpcResponse::LnSend),
-                    )
+                    .send(ln_payment_updates(client, payment.payment_type).await.map(
+                        |(operation_id, updates)| RpcResponse::LnSend {
+                            operation_id,
+                            updates,
+                        },
+                    ))
                     .map_err(|_| warn!("RPC receiver dropped before response was sent"));
             }
             RpcRequest::LnReceive {
                 amount,
                 description,
+                expiry,
+                gateway_id,
             } => {
-                let gateway = client
-                    .get_first_module::<LightningClientModule>()
-                    .list_gateways()
-                    .await
-                    .first()
-                    .map(|gw| gw.info.clone());
+                let gateway = match select_gateway(client, gateway_id).await {
+                    Ok(Some(gateway)) => Some(gateway),
+                    Ok(None) => {
+                        let _ = response_sender
+                            .send(Err(no_usable_gateway_error()))
+                            .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                        continue;
+                    }
+                    Err(e) => {
+                        let _ = response_sender
+                            .send(Err(e))
+                            .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                        continue;
+                    }
+                };
+                if let Err(e) = description.validate() {
+                    let _ = response_sender
+                        .send(Err(e.into()))
+                        .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                    continue;
+                }
+                let direct_description;
+                let description_hash;
+                let (invoice_description, extra_meta) = match description {
+                    InvoiceDescription::Direct(description) => {
+                        direct_description = lightning_invoice::Description::new(description)
+                            .expect("Length was validated");
+                        (
+                            Bolt11InvoiceDescription::Direct(&direct_description),
+                            LnReceiveExtraMeta::default(),
+                        )
+                    }
+                    InvoiceDescription::Hash(description) => {
+                        description_hash =
+                            lightning_invoice::Sha256(sha256::Hash::hash(description.as_bytes()));
+                        (
+                            Bolt11InvoiceDescription::Hash(&description_hash),
+                            LnReceiveExtraMeta {
+                                description: Some(description),
+                            },
+                        )
+                    }
+                };
                 let (operation_id, invoice, _) = match client
                     .get_first_module::<LightningClientModule>()
                     .create_bolt11_invoice(
                         amount,
-                        Bolt11InvoiceDescription::Direct(
-                            &lightning_invoice::Description::new(description)
-                                .expect("FIXME: handle invalid descriptions"),
-                        ),
-                        None,
-                        (),
+                        invoice_description,
+                        Some(expiry.as_secs()),
+                        extra_meta,
                         gateway,
                     )
                     .await
@@ -362,98 +2102,183 @@ async fn run_client(mut rpc: mpsc::Receiver<RpcCall>) {
                     }
                 };
 
-                let (await_paid_sender, await_paid_receiver) = watch::channel(false);
-                let mut subscription = client
+                let updates = client
                     .get_first_module::<LightningClientModule>()
                     .subscribe_ln_receive(operation_id)
                     .await
                     .expect("subscribing to a just created operation can't fail")
-                    .into_stream();
-                spawn("waiting for invoice being paid", async move {
-                    while let Some(state) = subscription.next().await {
-                        if state == fedimint_ln_client::LnReceiveState::Funded {
-                            let _ = await_paid_sender.send(true);
-                        }
-                    }
-                });
+                    .into_stream()
+                    .map(LnReceiveProgress::from);
 
                 let _ = response_sender
                     .send(Ok(RpcResponse::LnReceive {
+                        operation_id,
                         invoice: invoice.to_string(),
-                        await_paid: await_paid_receiver,
+                        expires_at: invoice_expires_at(&invoice),
+                        updates: Box::pin(updates),
                     }))
                     .map_err(|_| warn!("RPC receiver dropped before response was sent"));
             }
-            RpcRequest::ListTransactions => {
-                let transactions = client
-                    .operation_log()
-                    .list_operations(100, None)
-                    .await
-                    .into_iter()
-                    .map(|(key, op_log)| {
-                        let (amount_msat, description) = match op_log.operation_module_kind() {
-                            "mint" => {
-                                let meta = op_log.meta::<MintOperationMeta>();
-                                match meta.variant {
-                                    MintOperationMetaVariant::Reissuance { .. } => {
-                                        (meta.amount.msats as i64, None)
-                                    }
-                                    MintOperationMetaVariant::SpendOOB { .. } => {
-                                        (-(meta.amount.msats as i64), None)
-                                    }
-                                }
-                            }
-                            "ln" => match op_log.meta::<LightningOperationMeta>().variant {
-                                LightningOperationMetaVariant::Receive { invoice, .. } => {
-                                    let amount = invoice
-                                        .amount_milli_satoshis()
-                                        .expect("We don't create 0 amount invoices")
-                                        as i64;
-                                    let description = match invoice.description() {
-                                        Bolt11InvoiceDescription::Direct(description) => {
-                                            Some(description.to_string())
-                                        }
-                                        Bolt11InvoiceDescription::Hash(_) => None,
-                                    };
-
-                                    (amount, description)
-                                }
-                                LightningOperationMetaVariant::Pay(LightningOperationMetaPay {
-                                    invoice,
-                                    ..
-                                }) => {
-                                    // TODO: add fee
-                                    let amount = -(invoice
-                                        .amount_milli_satoshis()
-                                        .expect("Can't pay 0 amount invoices")
-                                        as i64);
-                                    let description = match invoice.description() {
-                                        Bolt11InvoiceDescription::Direct(description) => {
-                                            Some(description.to_string())
-                                        }
-                                        Bolt11InvoiceDescription::Hash(_) => None,
-                                    };
-
-                                    (amount, description)
-                                }
-                                LightningOperationMetaVariant::Claim { .. } => {
-                                    panic!("We'll never generate such operations")
-                                }
-                            },
-                            _ => panic!("Unsupported module"),
-                        };
+            RpcRequest::SubscribeTransactions => {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                spawn(
+                    "transaction list updates",
+                    watch_transactions(client, transaction_watchers, sender),
+                );
+                let _ = response_sender
+                    .send(Ok(RpcResponse::SubscribeTransactions(Box::pin(
+                        UnboundedReceiverStream::new(receiver),
+                    ))))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::SetOperationLabel {
+                operation_id,
+                label,
+            } => {
+                let label = label.normalized();
+                let mut dbtx = client.db().begin_transaction().await;
+                if label.is_empty() {
+                    dbtx.remove_entry(&OperationLabelKey(operation_id)).await;
+                } else {
+                    dbtx.insert_entry(&OperationLabelKey(operation_id), &label)
+                        .await;
+                }
+                dbtx.commit_tx().await;
 
-                        Transaction {
-                            timestamp: key.creation_time,
-                            operation_id: key.operation_id,
-                            operation_kind: op_log.operation_module_kind().to_owned(),
-                            amount_msat,
-                            description,
+                let _ = response_sender
+                    .send(Ok(RpcResponse::SetOperationLabel))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::BalanceHistory => {
+                let history = balance_history(list_all_transactions(client).await);
+                let _ = response_sender
+                    .send(Ok(RpcResponse::BalanceHistory(history)))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::NoteHoldings => {
+                let _ = response_sender
+                    .send(Ok(RpcResponse::NoteHoldings(note_holdings(client).await)))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::ConsolidateNotes => {
+                // Waits for the federation, so don't hold up other requests
+                spawn("note consolidation", async move {
+                    let _ = response_sender
+                        .send(
+                            consolidate_notes(client, consolidating)
+                                .await
+                                .map(RpcResponse::ConsolidateNotes),
+                        )
+                        .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+                });
+            }
+            RpcRequest::SubscribeNoteConsolidation => {
+                // Yields the current state first and then every change
+                let updates = futures::stream::unfold(
+                    (consolidating.subscribe(), true),
+                    |(mut receiver, first)| async move {
+                        if !first {
+                            receiver.changed().await.ok()?;
                         }
-                    })
+                        let running = *receiver.borrow_and_update();
+                        Some((running, (receiver, false)))
+                    },
+                );
+                let _ = response_sender
+                    .send(Ok(RpcResponse::SubscribeNoteConsolidation(Box::pin(
+                        updates,
+                    ))))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::GetNoteConsolidationThreshold => {
+                let threshold = client
+                    .db()
+                    .begin_transaction_nc()
+                    .await
+                    .get_value(&NoteConsolidationThresholdKey)
+                    .await;
+                let _ = response_sender
+                    .send(Ok(RpcResponse::GetNoteConsolidationThreshold(threshold)))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::SetNoteConsolidationThreshold(threshold) => {
+                let mut dbtx = client.db().begin_transaction().await;
+                match threshold {
+                    Some(threshold) => {
+                        dbtx.insert_entry(&NoteConsolidationThresholdKey, &threshold)
+                            .await;
+                    }
+                    None => {
+                        dbtx.remove_entry(&NoteConsolidationThresholdKey).await;
+                    }
+                }
+                dbtx.commit_tx().await;
+
+                let _ = response_sender
+                    .send(Ok(RpcResponse::SetNoteConsolidationThreshold))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::ListGateways => {
+                let _ = response_sender
+                    .send(Ok(RpcResponse::ListGateways(
+                        list_gateway_infos(client).await,
+                    )))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::SetGatewayVettingPolicy(vetting_policy) => {
+                let mut dbtx = client.db().begin_transaction().await;
+                dbtx.insert_entry(&GatewayVettingPolicyKey, &vetting_policy)
+                    .await;
+                dbtx.commit_tx().await;
+
+                let _ = response_sender
+                    .send(Ok(RpcResponse::SetGatewayVettingPolicy))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::LnurlAuthSign { domain, k1 } => {
+                let _ = response_sender
+                    .send(
+                        sign_lnurl_auth(client, &domain, k1)
+                            .await
+                            .map(|(key, sig)| RpcResponse::LnurlAuthSign { key, sig }),
+                    )
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::SetPreferredGateway(gateway_id) => {
+                let mut dbtx = client.db().begin_transaction().await;
+                match gateway_id {
+                    Some(gateway_id) => {
+                        dbtx.insert_entry(&PreferredGatewayKey, &gateway_id).await;
+                    }
+                    None => {
+                        dbtx.remove_entry(&PreferredGatewayKey).await;
+                    }
+                }
+                dbtx.commit_tx().await;
+
+                let _ = response_sender
+                    .send(Ok(RpcResponse::SetPreferredGateway))
+                    .map_err(|_| warn!("RPC receiver dropped before response was sent"));
+            }
+            RpcRequest::ListTransactions(query) => {
+                let mut transactions = list_all_transactions(client)
+                    .await
+                    .into_iter()
+                    .filter(|tx| query.filter.matches(tx))
                     .collect::<Vec<_>>();
+                query.sort.sort(&mut transactions);
+
+                let total = transactions.len();
+                let transactions = transactions
+                    .into_iter()
+                    .skip(query.offset)
+                    .take(query.limit)
+                    .collect();
                 let _ = response_sender
-                    .send(Ok(RpcResponse::ListTransactions(transactions)))
+                    .send(Ok(RpcResponse::ListTransactions(TransactionPage {
+                        transactions,
+                        total,
+                    })))
                     .map_err(|_| warn!("RPC receiver dropped before response was sent"));
             }
             req => {
@@ -518,10 +2343,29 @@ impl ClientRpc {
         }
     }
 
-    pub async fn ecash_send(&self, amount: Amount) -> anyhow::Result<OOBNotes, RpcError> {
+    /// Spends ecash out of band, `reclaim_after` overrides the wallet's default
+    /// time after which unredeemed notes are reclaimed. With `exact` notes are
+    /// exchanged with the federation first instead of overpaying, otherwise
+    /// the federation isn't contacted at all. Without `amount` all notes are
+    /// spent.
+    pub async fn ecash_send(
+        &self,
+        amount: Option<Amount>,
+        reclaim_after: Option<Duration>,
+        exact: bool,
+        include_invite: bool,
+    ) -> anyhow::Result<OOBNotes, RpcError> {
         let (response_sender, response_receiver) = oneshot::channel();
         self.sender
-            .send((RpcRequest::EcashSend(amount), response_sender))
+            .send((
+                RpcRequest::EcashSend {
+                    amount,
+                    reclaim_after,
+                    exact,
+                    include_invite,
+                },
+                response_sender,
+            ))
             .await
             .expect("Client has stopped");
         let response = response_receiver.await.expect("Client has stopped")?;
@@ -531,28 +2375,163 @@ impl ClientRpc {
         }
     }
 
-    pub async fn ecash_receive(&self, invoice: String) -> anyhow::Result<Amount, RpcError> {
+    /// Returns the wallet's default time after which unredeemed ecash spends
+    /// are reclaimed
+    pub async fn get_ecash_reclaim_timeout(&self) -> anyhow::Result<Duration, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::GetEcashReclaimTimeout, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::GetEcashReclaimTimeout(timeout) => Ok(timeout),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Sets the wallet's default time after which unredeemed ecash spends are
+    /// reclaimed
+    pub async fn set_ecash_reclaim_timeout(
+        &self,
+        timeout: Duration,
+    ) -> anyhow::Result<(), RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::SetEcashReclaimTimeout(timeout), response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::SetEcashReclaimTimeout => Ok(()),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Tries to take back the notes of an ecash spend the recipient didn't
+    /// redeem yet. Returns `false` if it was too late.
+    pub async fn reclaim_ecash(&self, operation_id: OperationId) -> anyhow::Result<bool, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::ReclaimEcash(operation_id), response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::ReclaimEcash(reclaimed) => Ok(reclaimed),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Decodes `notes` without redeeming them
+    pub async fn preview_ecash(&self, notes: String) -> anyhow::Result<EcashPreview, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::PreviewEcash(notes), response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::PreviewEcash(preview) => Ok(preview),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Starts redeeming `notes`, the returned stream tells when the notes are
+    /// final
+    pub async fn ecash_receive(
+        &self,
+        notes: String,
+    ) -> anyhow::Result<(EcashRedemption, BoxStream<'static, EcashReceiveProgress>), RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::EcashReceive(notes), response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::EcashReceive {
+                operation_id,
+                amount,
+                updates,
+            } => Ok((
+                EcashRedemption {
+                    operation_id,
+                    amount,
+                },
+                updates,
+            )),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Returns the largest amount the balance can pay through the gateway
+    /// `gateway_id`, or the pinned gateway if none is given, after its fee
+    pub async fn max_payable(
+        &self,
+        gateway_id: Option<PublicKey>,
+    ) -> anyhow::Result<u64, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::MaxPayable { gateway_id }, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::MaxPayable(amount_msat) => Ok(amount_msat),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Decodes `invoice` and estimates the fee of paying it through the
+    /// gateway `gateway_id`, or the pinned gateway if none is given.
+    pub async fn preview_invoice(
+        &self,
+        invoice: String,
+        gateway_id: Option<PublicKey>,
+    ) -> anyhow::Result<InvoicePreview, RpcError> {
         let (response_sender, response_receiver) = oneshot::channel();
         self.sender
-            .send((RpcRequest::EcashReceive(invoice), response_sender))
+            .send((
+                RpcRequest::PreviewInvoice {
+                    invoice,
+                    gateway_id,
+                },
+                response_sender,
+            ))
             .await
             .expect("Client has stopped");
         let response = response_receiver.await.expect("Client has stopped")?;
         match response {
-            RpcResponse::EcashReceive(amount) => Ok(amount),
+            RpcResponse::PreviewInvoice(preview) => Ok(preview),
             _ => Err(RpcError::InvalidResponse),
         }
     }
 
-    pub async fn ln_send(&self, invoice: String) -> anyhow::Result<(), RpcError> {
+    /// Pays `invoice` through the gateway `gateway_id`, or the pinned gateway
+    /// if none is given.
+    pub async fn ln_send(
+        &self,
+        invoice: String,
+        gateway_id: Option<PublicKey>,
+    ) -> anyhow::Result<(OperationId, BoxStream<'static, LnPaymentState>), RpcError> {
         let (response_sender, response_receiver) = oneshot::channel();
         self.sender
-            .send((RpcRequest::LnSend(invoice), response_sender))
+            .send((
+                RpcRequest::LnSend {
+                    invoice,
+                    gateway_id,
+                },
+                response_sender,
+            ))
             .await
             .expect("Client has stopped");
         let response = response_receiver.await.expect("Client has stopped")?;
         match response {
-            RpcResponse::LnSend => Ok(()),
+            RpcResponse::LnSend {
+                operation_id,
+                updates,
+            } => Ok((operation_id, updates)),
             _ => Err(RpcError::InvalidResponse),
         }
     }
@@ -560,14 +2539,18 @@ impl ClientRpc {
     pub async fn ln_receive(
         &self,
         amount_msat: u64,
-        description: String,
-    ) -> anyhow::Result<(String, watch::Receiver<bool>), RpcError> {
+        description: InvoiceDescription,
+        expiry: Duration,
+        gateway_id: Option<PublicKey>,
+    ) -> anyhow::Result<(LnReceiveInvoice, BoxStream<'static, LnReceiveProgress>), RpcError> {
         let (response_sender, response_receiver) = oneshot::channel();
         self.sender
             .send((
                 RpcRequest::LnReceive {
                     amount: Amount::from_msats(amount_msat),
                     description,
+                    expiry,
+                    gateway_id,
                 },
                 response_sender,
             ))
@@ -576,22 +2559,239 @@ impl ClientRpc {
         let response = response_receiver.await.expect("Client has stopped")?;
         match response {
             RpcResponse::LnReceive {
+                operation_id,
                 invoice,
-                await_paid,
-            } => Ok((invoice, await_paid)),
+                expires_at,
+                updates,
+            } => Ok((
+                LnReceiveInvoice {
+                    operation_id,
+                    invoice,
+                    expires_at,
+                },
+                updates,
+            )),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Returns one page of the transactions matching `query`, filtering and
+    /// sorting is applied to the whole operation log before paginating.
+    pub async fn list_transactions(
+        &self,
+        query: TransactionQuery,
+    ) -> anyhow::Result<TransactionPage, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::ListTransactions(query), response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::ListTransactions(page) => Ok(page),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Streams transactions as they are created or their status changes.
+    pub async fn subscribe_transactions(
+        &self,
+    ) -> anyhow::Result<BoxStream<'static, Transaction>, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::SubscribeTransactions, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::SubscribeTransactions(stream) => Ok(stream),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Attaches a label and tags to an operation, replacing previous ones. An
+    /// empty label without tags removes it.
+    pub async fn set_operation_label(
+        &self,
+        operation_id: OperationId,
+        label: OperationLabel,
+    ) -> anyhow::Result<(), RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((
+                RpcRequest::SetOperationLabel {
+                    operation_id,
+                    label,
+                },
+                response_sender,
+            ))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::SetOperationLabel => Ok(()),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Returns the balance after every operation that changed it, oldest first
+    pub async fn balance_history(&self) -> anyhow::Result<Vec<BalancePoint>, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::BalanceHistory, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::BalanceHistory(history) => Ok(history),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Returns how many notes the wallet holds of each denomination
+    pub async fn note_holdings(&self) -> anyhow::Result<Vec<(Amount, usize)>, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::NoteHoldings, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::NoteHoldings(holdings) => Ok(holdings),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Reissues all notes into fewer ones and reports how many were merged
+    pub async fn consolidate_notes(&self) -> anyhow::Result<NoteConsolidation, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::ConsolidateNotes, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::ConsolidateNotes(consolidation) => Ok(consolidation),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Yields whether notes are being consolidated, which holds up the balance
+    /// until the federation reissued them
+    pub async fn subscribe_note_consolidation(
+        &self,
+    ) -> anyhow::Result<BoxStream<'static, bool>, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::SubscribeNoteConsolidation, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::SubscribeNoteConsolidation(updates) => Ok(updates),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Returns the number of notes above which they are consolidated
+    /// automatically, `None` if automatic consolidation is off
+    pub async fn get_note_consolidation_threshold(&self) -> anyhow::Result<Option<u64>, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::GetNoteConsolidationThreshold, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::GetNoteConsolidationThreshold(threshold) => Ok(threshold),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    pub async fn set_note_consolidation_threshold(
+        &self,
+        threshold: Option<u64>,
+    ) -> anyhow::Result<(), RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((
+                RpcRequest::SetNoteConsolidationThreshold(threshold),
+                response_sender,
+            ))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::SetNoteConsolidationThreshold => Ok(()),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    pub async fn list_gateways(&self) -> anyhow::Result<GatewayList, RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::ListGateways, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::ListGateways(gateways) => Ok(gateways),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    pub async fn set_gateway_vetting_policy(
+        &self,
+        vetting_policy: GatewayVettingPolicy,
+    ) -> anyhow::Result<(), RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((
+                RpcRequest::SetGatewayVettingPolicy(vetting_policy),
+                response_sender,
+            ))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::SetGatewayVettingPolicy => Ok(()),
+            _ => Err(RpcError::InvalidResponse),
+        }
+    }
+
+    /// Signs a LNURL-auth challenge for `domain`, returning the linking
+    /// public key and signature to send to the service.
+    pub async fn lnurl_auth_sign(
+        &self,
+        domain: String,
+        k1: [u8; 32],
+    ) -> anyhow::Result<(String, String), RpcError> {
+        let (response_sender, response_receiver) = oneshot::channel();
+        self.sender
+            .send((RpcRequest::LnurlAuthSign { domain, k1 }, response_sender))
+            .await
+            .expect("Client has stopped");
+        let response = response_receiver.await.expect("Client has stopped")?;
+        match response {
+            RpcResponse::LnurlAuthSign { key, sig } => Ok((key, sig)),
             _ => Err(RpcError::InvalidResponse),
         }
     }
 
-    pub async fn list_transactions(&self) -> anyhow::Result<Vec<Transaction>, RpcError> {
+    /// Pins the gateway used for Lightning payments of this wallet, `None`
+    /// lets the wallet pick any available gateway again.
+    pub async fn set_preferred_gateway(
+        &self,
+        gateway_id: Option<PublicKey>,
+    ) -> anyhow::Result<(), RpcError> {
         let (response_sender, response_receiver) = oneshot::channel();
         self.sender
-            .send((RpcRequest::ListTransactions, response_sender))
+            .send((RpcRequest::SetPreferredGateway(gateway_id), response_sender))
             .await
             .expect("Client has stopped");
         let response = response_receiver.await.expect("Client has stopped")?;
         match response {
-            RpcResponse::ListTransactions(transactions) => Ok(transactions),
+            RpcResponse::SetPreferredGateway => Ok(()),
             _ => Err(RpcError::InvalidResponse),
         }
     }
@@ -624,3 +2824,134 @@ impl ClientRpc {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lnurl_auth_path_matches_lud05() {
+        let hashing_key =
+            hex::decode("7d417a6a5e9a6a4a879aeaba11a11838764c8fa2b959c242d43dea682b3e409b")
+                .unwrap()
+                .try_into()
+                .unwrap();
+
+        assert_eq!(
+            lnurl_auth_path_indices(&hashing_key, "site.com"),
+            [1588488367, 2659270754, 38110259, 4136336762]
+        );
+    }
+
+    #[test]
+    fn lnurl_auth_signature_verifies() {
+        let entropy = [7; 64];
+        let k1 = [42; 32];
+        let (public_key, signature) = lnurl_auth_signature(&entropy, "site.com", k1).unwrap();
+
+        Secp256k1::new()
+            .verify_ecdsa(&Message::from_slice(&k1).unwrap(), &signature, &public_key)
+            .unwrap();
+
+        // The linking key only depends on the domain
+        let (same_key, _) = lnurl_auth_signature(&entropy, "site.com", [1; 32]).unwrap();
+        let (other_key, _) = lnurl_auth_signature(&entropy, "other.com", k1).unwrap();
+        assert_eq!(public_key, same_key);
+        assert_ne!(public_key, other_key);
+    }
+
+    #[test]
+    fn direct_description_length_is_limited() {
+        let direct = |length: usize| InvoiceDescription::Direct("a".repeat(length));
+
+        assert_eq!(direct(0).validate(), Ok(()));
+        assert_eq!(
+            direct(InvoiceDescription::MAX_DIRECT_LENGTH).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            direct(InvoiceDescription::MAX_DIRECT_LENGTH + 1).validate(),
+            Err(InvoiceDescriptionError::TooLong { length: 640 })
+        );
+        // The limit is in bytes, not characters
+        assert_eq!(
+            InvoiceDescription::Direct("€".repeat(214)).validate(),
+            Err(InvoiceDescriptionError::TooLong { length: 642 })
+        );
+    }
+
+    #[test]
+    fn hashed_description_length_is_unlimited() {
+        assert_eq!(
+            InvoiceDescription::Hash("a".repeat(10_000)).validate(),
+            Ok(())
+        );
+    }
+
+    fn fees(base_msat: u32, proportional_millionths: u32) -> RoutingFees {
+        RoutingFees {
+            base_msat,
+            proportional_millionths,
+        }
+    }
+
+    #[test]
+    fn gateway_fee_matches_fedimint() {
+        // 1_000_000 / 3_000 = 333, so fedimint charges 1/333 of the amount
+        // instead of 0.3%
+        assert_eq!(
+            gateway_fee_msat(&fees(1_000, 3_000), 1_000_000),
+            1_000 + 3_003
+        );
+        assert_eq!(gateway_fee_msat(&fees(1_000, 0), 1_000_000), 1_000);
+        assert_eq!(gateway_fee_msat(&fees(0, 10_000), 999), 9);
+    }
+
+    #[test]
+    fn gateway_fee_above_100_percent() {
+        assert_eq!(gateway_fee_msat(&fees(0, 2_000_000), 1_000), 2_000);
+        assert_eq!(gateway_fee_msat(&fees(1, u32::MAX), u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn max_payable_leaves_room_for_the_fee() {
+        assert_eq!(max_payable_msat(1_000, &fees(0, 0)), 1_000);
+        assert_eq!(max_payable_msat(1_000, &fees(10, 0)), 990);
+        assert_eq!(max_payable_msat(5, &fees(10, 0)), 0);
+        assert_eq!(max_payable_msat(1_000_000, &fees(0, 3_000)), 997_006);
+        assert_eq!(max_payable_msat(100_000, &fees(1_000, 10_000)), 98_020);
+        assert_eq!(max_payable_msat(3_000, &fees(0, 2_000_000)), 1_000);
+    }
+
+    #[test]
+    fn max_payable_is_largest_fitting_amount() {
+        for balance_msat in [0, 1, 999, 1_000, 123_456, 10_000_000, 2_100_000_000_000] {
+            for fees in [
+                fees(0, 0),
+                fees(1_000, 0),
+                fees(0, 1),
+                fees(2, 3_000),
+                fees(0, 1_000_000),
+            ] {
+                let amount = max_payable_msat(balance_msat, &fees);
+                if balance_msat < u64::from(fees.base_msat) {
+                    // Not even an empty payment fits
+                    assert_eq!(amount, 0);
+                    continue;
+                }
+                assert!(amount + gateway_fee_msat(&fees, amount) <= balance_msat);
+                assert!(amount + 1 + gateway_fee_msat(&fees, amount + 1) > balance_msat);
+            }
+        }
+    }
+
+    #[test]
+    fn max_payable_does_not_overflow() {
+        assert_eq!(max_payable_msat(u64::MAX, &fees(0, 0)), u64::MAX);
+        assert_eq!(
+            max_payable_msat(u64::MAX, &fees(0, 1_000_000)),
+            u64::MAX / 2
+        );
+        assert!(max_payable_msat(u64::MAX, &fees(u32::MAX, u32::MAX)) > 0);
+    }
+}
@@ -6,9 +6,9 @@ use tracing::info;
 use crate::client::ClientRpc;
 use crate::components::create_wallet::CreateWallet;
 use crate::components::service_worker::ServiceWorker;
-use crate::components::{Footer, Joined, Logo, SubmitForm, WalletSelector};
+use crate::components::{Footer, Joined, Logo, RedeemEcash, SubmitForm, WalletSelector};
 use crate::context::provide_client_context;
-use crate::utils::empty_view;
+use crate::utils::{empty_view, PendingRedemption};
 
 //
 // App component
@@ -59,6 +59,28 @@ pub fn App() -> impl IntoView {
                 || select_wallet == Some(Some(true)))
     };
 
+    // Redeeming e-cash of another federation reloads the app to create a new
+    // wallet that joins it, which then goes through the usual steps
+    let pending_redemption = store_value(PendingRedemption::get());
+    let (redeem_notes, set_redeem_notes) = create_signal(None::<String>);
+    if let Some(pending) = pending_redemption.get_value() {
+        select_wallet_action.dispatch(pending.wallet_name);
+    }
+    create_effect(move |_| {
+        if select_wallet_action.value().get() == Some(Some(false)) {
+            if let Some(pending) = pending_redemption.get_value() {
+                join_action.dispatch(pending.invite_code);
+            }
+        }
+    });
+    create_effect(move |_| {
+        if show_wallet() {
+            if let Some(pending) = pending_redemption.try_update_value(Option::take).flatten() {
+                set_redeem_notes.set(Some(pending.notes));
+            }
+        }
+    });
+
     view! {
       <ServiceWorker path="./service-worker.js" />
 
@@ -133,6 +155,7 @@ pub fn App() -> impl IntoView {
               when=show_wallet
               fallback=|| empty_view()
             >
+              {move || redeem_notes.get().map(|notes| view! { <RedeemEcash notes=notes /> })}
               <Joined />
             </Show>
           </main>
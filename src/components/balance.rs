@@ -2,6 +2,7 @@ use fedimint_core::Amount;
 use leptos::logging::*;
 use leptos::*;
 
+use crate::components::{BalanceHistory, NoteHoldings};
 use crate::context::ClientContext;
 
 //
@@ -24,6 +25,29 @@ pub fn Balance(#[prop(optional, into)] class: String) -> impl IntoView {
             create_signal_from_stream(balance_stream)
         },
     );
+    let balance_amount = Signal::derive(move || balance_resource.get().and_then(|b| b.get()));
+
+    // Consolidating notes spends the entire balance until the federation
+    // reissued them
+    let consolidation_resource = create_local_resource(
+        || (),
+        move |()| async move {
+            let updates = match client.get_value().subscribe_note_consolidation().await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    warn!("client could not subscribe to note consolidation: {e:?}");
+                    std::future::pending().await
+                }
+            };
+            create_signal_from_stream(updates)
+        },
+    );
+    let consolidating = Signal::derive(move || {
+        consolidation_resource
+            .get()
+            .and_then(|running| running.get())
+            .unwrap_or(false)
+    });
     let balance = move || match balance_resource.get() {
         None => view! { <p>"Loading..."</p> }.into_view(),
         Some(balance) => {
@@ -32,10 +56,40 @@ pub fn Balance(#[prop(optional, into)] class: String) -> impl IntoView {
         }
     };
 
+    let (show_history, set_show_history) = create_signal(false);
+    let (show_notes, set_show_notes) = create_signal(false);
+
     view! {
       <div class=class>
         <h2 class="text-lg md:text-xl leading-tight w-full font-body font-semibold md:pb-4 mb-4 text-gray-400 border-b-2 border-gray-200">"Balance"</h2>
-        <h3 class="text-xl md:text-4xl">{balance}</h3>
+        <div class="flex items-baseline justify-between">
+          <h3 class="text-xl md:text-4xl">{balance}</h3>
+          <div class="flex gap-4">
+            <button
+              class="text-sm text-blue-500"
+              on:click=move |_| set_show_notes.update(|show| *show = !*show)
+            >
+              {move || if show_notes.get() { "Hide notes" } else { "Show notes" }}
+            </button>
+            <button
+              class="text-sm text-blue-500"
+              on:click=move |_| set_show_history.update(|show| *show = !*show)
+            >
+              {move || if show_history.get() { "Hide history" } else { "Show history" }}
+            </button>
+          </div>
+        </div>
+        <Show when=move || consolidating.get()>
+          <p class="text-sm text-gray-400">
+            "Consolidating notes, the balance is available again once the federation reissued them…"
+          </p>
+        </Show>
+        <Show when=move || show_notes.get()>
+          <NoteHoldings balance=balance_amount consolidating=consolidating class="mt-4" />
+        </Show>
+        <Show when=move || show_history.get()>
+          <BalanceHistory class="mt-4" />
+        </Show>
       </div>
     }
 }
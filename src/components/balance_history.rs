@@ -0,0 +1,161 @@
+use std::time::{Duration, SystemTime};
+
+use fedimint_core::time::now;
+use leptos::*;
+
+use crate::client::BalancePoint;
+use crate::components::{ErrorBlock, LoaderIcon, SegmentedButton};
+use crate::context::ClientContext;
+use crate::utils::format_timestamp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HistoryRange {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl HistoryRange {
+    const ALL: [HistoryRange; 4] = [
+        HistoryRange::Day,
+        HistoryRange::Week,
+        HistoryRange::Month,
+        HistoryRange::All,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            HistoryRange::Day => "Day",
+            HistoryRange::Week => "Week",
+            HistoryRange::Month => "Month",
+            HistoryRange::All => "All",
+        }
+    }
+
+    fn duration(self) -> Option<Duration> {
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        match self {
+            HistoryRange::Day => Some(DAY),
+            HistoryRange::Week => Some(DAY * 7),
+            HistoryRange::Month => Some(DAY * 30),
+            HistoryRange::All => None,
+        }
+    }
+}
+
+//
+// Balance history component
+// Chart of the balance reconstructed from the operation log
+//
+#[component]
+pub fn BalanceHistory(#[prop(optional, into)] class: String) -> impl IntoView {
+    const DEFAULT_RANGE: HistoryRange = HistoryRange::Week;
+
+    let ClientContext { client, .. } = expect_context::<ClientContext>();
+
+    let (range, set_range) = create_signal(DEFAULT_RANGE);
+    let history_resource = create_resource(
+        || (),
+        move |()| async move { client.get_value().balance_history().await },
+    );
+
+    let chart = move || {
+        history_resource.get().map(|history| match history {
+            Ok(history) => {
+                let end = now();
+                let start = match range.get().duration() {
+                    Some(duration) => end - duration,
+                    None => history.first().map_or(end, |point| point.timestamp),
+                };
+                view! { <BalanceChart history=history start=start end=end /> }.into_view()
+            }
+            Err(e) => view! {
+                <ErrorBlock>{format!("Failed to load balance history: {e}")}</ErrorBlock>
+            }
+            .into_view(),
+        })
+    };
+
+    view! {
+        <div class=class>
+            <SegmentedButton
+                active_idx=HistoryRange::ALL.iter().position(|r| *r == DEFAULT_RANGE).unwrap_or_default()
+                segments=HistoryRange::ALL.iter().map(|range| range.name().to_owned()).collect()
+                on_change=move |idx| set_range.set(HistoryRange::ALL[idx])
+            />
+            <Suspense fallback=move || view! { <LoaderIcon /> }>
+                {chart}
+            </Suspense>
+        </div>
+    }
+}
+
+#[component]
+fn BalanceChart(history: Vec<BalancePoint>, start: SystemTime, end: SystemTime) -> impl IntoView {
+    const WIDTH: f64 = 600.0;
+    const HEIGHT: f64 = 200.0;
+
+    let start_balance = history
+        .iter()
+        .take_while(|point| point.timestamp <= start)
+        .last()
+        .map_or(0, |point| point.balance_msat);
+    let mut steps = vec![(start, start_balance)];
+    steps.extend(
+        history
+            .iter()
+            .filter(|point| start < point.timestamp && point.timestamp <= end)
+            .map(|point| (point.timestamp, point.balance_msat)),
+    );
+    let end_balance = steps.last().map_or(start_balance, |(_, balance)| *balance);
+
+    let min_balance = steps.iter().map(|(_, b)| *b).min().unwrap_or(0).min(0);
+    let max_balance = steps.iter().map(|(_, b)| *b).max().unwrap_or(0);
+    let balance_span = (max_balance - min_balance).max(1) as f64;
+    let time_span = end
+        .duration_since(start)
+        .unwrap_or_default()
+        .as_secs_f64()
+        .max(1.0);
+
+    let x = |time: SystemTime| {
+        time.duration_since(start).unwrap_or_default().as_secs_f64() / time_span * WIDTH
+    };
+    let y = |balance: i64| HEIGHT - (balance - min_balance) as f64 / balance_span * HEIGHT;
+
+    // The balance only changes with operations, so draw a step function
+    let mut path = format!("M 0 {:.1}", y(start_balance));
+    for (time, balance) in steps.iter().skip(1) {
+        path.push_str(&format!(" H {:.1} V {:.1}", x(*time), y(*balance)));
+    }
+    path.push_str(&format!(" H {WIDTH:.1}"));
+
+    view! {
+        <svg
+            viewBox=format!("0 0 {WIDTH} {HEIGHT}")
+            preserveAspectRatio="none"
+            class="w-full h-48 bg-slate-50 border-y border-slate-300"
+        >
+            <path
+                d=path
+                fill="none"
+                stroke="currentColor"
+                stroke-width="2"
+                vector-effect="non-scaling-stroke"
+                class="text-blue-500"
+            />
+        </svg>
+        <div class="flex justify-between text-xs text-gray-400 mt-1">
+            <span>{format_timestamp(start)}</span>
+            <span>{format!("{min_balance} – {max_balance} msat")}</span>
+            <span>{format_timestamp(end)}</span>
+        </div>
+        <p class="text-sm text-gray-600 mt-2">
+            {format!(
+                "{start_balance} msat → {end_balance} msat ({:+} msat)",
+                end_balance - start_balance
+            )}
+        </p>
+    }
+}
@@ -0,0 +1,218 @@
+use std::str::FromStr;
+
+use leptos::*;
+use secp256k1_zkp::PublicKey;
+
+use crate::client::GatewayInfo;
+use crate::components::{ErrorBlock, LoaderIcon, WarningBlock};
+use crate::context::ClientContext;
+use crate::db::GatewayVettingPolicy;
+use crate::utils::format_timestamp;
+
+//
+// Gateway selector component
+// Lets the user override the gateway used for a single payment and pin the
+// default one of the wallet
+//
+#[component]
+pub fn GatewaySelector<F>(on_change: F, #[prop(optional, into)] class: String) -> impl IntoView
+where
+    F: Fn(Option<PublicKey>) + 'static + Copy,
+{
+    let ClientContext { client, .. } = expect_context::<ClientContext>();
+
+    let (selected, set_selected) = create_signal(None::<PublicKey>);
+    let gateways_resource = create_resource(
+        || (),
+        move |()| async move { client.get_value().list_gateways().await },
+    );
+
+    let pin_action = create_action(move |gateway_id: &Option<PublicKey>| {
+        let gateway_id = *gateway_id;
+        async move {
+            let result = client.get_value().set_preferred_gateway(gateway_id).await;
+            gateways_resource.refetch();
+            result
+        }
+    });
+
+    let policy_action = create_action(move |vetting_policy: &GatewayVettingPolicy| {
+        let vetting_policy = *vetting_policy;
+        async move {
+            let result = client
+                .get_value()
+                .set_gateway_vetting_policy(vetting_policy)
+                .await;
+            gateways_resource.refetch();
+            result
+        }
+    });
+
+    let gateways_view = move || {
+        gateways_resource.get().map(|gateways| match gateways {
+            Ok(list) => {
+                let vetting_policy = list.vetting_policy;
+                let gateways = list
+                    .gateways
+                    .into_iter()
+                    .filter(|gw| gw.usable)
+                    .collect::<Vec<_>>();
+                if gateways.is_empty() {
+                    return view! {
+                        <WarningBlock>
+                            "No usable Lightning gateway. The federation doesn't list any gateway \
+                            allowed by the vetting policy below, change it to use unvetted gateways."
+                        </WarningBlock>
+                        <VettingPolicySelect
+                            value=vetting_policy
+                            on_change=move |policy| policy_action.dispatch(policy)
+                        />
+                    }
+                    .into_view();
+                }
+
+                let preferred = gateways.iter().find(|gw| gw.preferred).cloned();
+                let default_label = match &preferred {
+                    Some(gateway) => format!("Pinned gateway ({})", gateway.lightning_alias),
+                    None => "Any available gateway".to_owned(),
+                };
+                let has_preferred = preferred.is_some();
+                let details = gateways.clone();
+
+                view! {
+                    <select
+                        class="w-full font-body text-gray-600 border-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                        on:change=move |ev| {
+                            let gateway_id = PublicKey::from_str(&event_target_value(&ev)).ok();
+                            set_selected.set(gateway_id);
+                            on_change(gateway_id);
+                        }
+                        prop:value=move || {
+                            selected.get().map(|gateway_id| gateway_id.to_string()).unwrap_or_default()
+                        }
+                    >
+                        <option value="">{default_label}</option>
+                        {gateways.into_iter().map(|gateway| {
+                            view! {
+                                <option value=gateway.gateway_id.to_string()>
+                                    {gateway_summary(&gateway)}
+                                </option>
+                            }
+                        }).collect_view()}
+                    </select>
+                    {move || {
+                        let gateway_id = selected.get()?;
+                        let gateway = details.iter().find(|gw| gw.gateway_id == gateway_id)?;
+                        let last_seen = gateway
+                            .last_seen
+                            .map_or("never".to_owned(), format_timestamp);
+                        Some(view! {
+                            <p class="text-xs text-gray-400 mt-1 break-all">
+                                {format!("Node {} · last seen {last_seen}", gateway.node_pub_key)}
+                            </p>
+                        })
+                    }}
+                    <VettingPolicySelect
+                        value=vetting_policy
+                        on_change=move |policy| policy_action.dispatch(policy)
+                    />
+                    <div class="flex gap-4 mt-1 text-sm">
+                        <Show when=move || selected.get().is_some()>
+                            <button
+                                class="text-blue-500"
+                                on:click=move |_| pin_action.dispatch(selected.get_untracked())
+                            >
+                                "Pin as default for this wallet"
+                            </button>
+                        </Show>
+                        <Show when=move || selected.get().is_none() && has_preferred>
+                            <button
+                                class="text-blue-500"
+                                on:click=move |_| pin_action.dispatch(None)
+                            >
+                                "Unpin default gateway"
+                            </button>
+                        </Show>
+                    </div>
+                }
+                .into_view()
+            }
+            Err(e) => view! {
+                <ErrorBlock>{format!("Failed to load gateways: {e}")}</ErrorBlock>
+            }
+            .into_view(),
+        })
+    };
+
+    view! {
+        <div class=class>
+            <p class="font-body text-gray-600 mb-2">"Lightning gateway"</p>
+            <Suspense fallback=move || view! { <LoaderIcon /> }>
+                {gateways_view}
+            </Suspense>
+            {move || {
+                pin_action.value().get().and_then(|result| result.err()).map(|e| {
+                    view! {
+                        <ErrorBlock class="mt-2">{format!("Failed to pin gateway: {e}")}</ErrorBlock>
+                    }
+                })
+            }}
+            {move || {
+                policy_action.value().get().and_then(|result| result.err()).map(|e| {
+                    view! {
+                        <ErrorBlock class="mt-2">
+                            {format!("Failed to change the vetting policy: {e}")}
+                        </ErrorBlock>
+                    }
+                })
+            }}
+        </div>
+    }
+}
+
+#[component]
+fn VettingPolicySelect<F>(value: GatewayVettingPolicy, on_change: F) -> impl IntoView
+where
+    F: Fn(GatewayVettingPolicy) + 'static,
+{
+    const POLICIES: [(GatewayVettingPolicy, &str); 3] = [
+        (GatewayVettingPolicy::VettedOnly, "Only use vetted gateways"),
+        (
+            GatewayVettingPolicy::VettedPreferred,
+            "Prefer vetted gateways",
+        ),
+        (GatewayVettingPolicy::Any, "Use any gateway"),
+    ];
+
+    view! {
+        <select
+            class="w-full mt-2 text-sm font-body text-gray-600 border-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+            on:change=move |ev| {
+                if let Ok(idx) = event_target_value(&ev).parse::<usize>() {
+                    on_change(POLICIES[idx].0);
+                }
+            }
+        >
+            {POLICIES.iter().enumerate().map(|(idx, (policy, name))| {
+                view! {
+                    <option value=idx.to_string() selected=*policy == value>{*name}</option>
+                }
+            }).collect_view()}
+        </select>
+    }
+}
+
+fn gateway_summary(gateway: &GatewayInfo) -> String {
+    format!(
+        "{} · {} msat + {} ppm{}{}",
+        gateway.lightning_alias,
+        gateway.fee_base_msat,
+        gateway.fee_proportional_millionths,
+        if gateway.vetted {
+            " · vetted"
+        } else {
+            " · unvetted"
+        },
+        if gateway.preferred { " · pinned" } else { "" },
+    )
+}
@@ -1,6 +1,6 @@
 use leptos::*;
 
-use crate::components::{Balance, Receive, Send, TxList};
+use crate::components::{Balance, Receive, Send, Settings, TxList};
 use crate::context::ClientContext;
 
 //
@@ -28,12 +28,10 @@ pub fn Joined() -> impl IntoView {
             .unwrap_or_else(|| "Loading...".into())
     };
 
-    let tab_change_signal = create_rw_signal(());
-
     let menu_items = vec![
         MenuItem {
             title: "Transactions".into(),
-            view: view! { <TxList update_signal=move || tab_change_signal.get() /> },
+            view: view! { <TxList /> },
         },
         MenuItem {
             title: "Send".into(),
@@ -43,6 +41,10 @@ pub fn Joined() -> impl IntoView {
             title: "Receive".into(),
             view: view! { <Receive /> },
         },
+        MenuItem {
+            title: "Settings".into(),
+            view: view! { <Settings /> },
+        },
     ];
 
     view! {
@@ -50,7 +52,6 @@ pub fn Joined() -> impl IntoView {
         <Balance class="my-12" />
         <Menu
             items=menu_items
-            on_tab_change=move || tab_change_signal.set(())
             initial_item=1
         />
     }
@@ -62,14 +63,7 @@ struct MenuItem {
 }
 
 #[component]
-fn Menu<F>(
-    items: Vec<MenuItem>,
-    on_tab_change: F,
-    #[prop(default = 0)] initial_item: usize,
-) -> impl IntoView
-where
-    F: Fn() + Copy + 'static,
-{
+fn Menu(items: Vec<MenuItem>, #[prop(default = 0)] initial_item: usize) -> impl IntoView {
     let (tab, set_tab) = create_signal(initial_item);
 
     view! {
@@ -79,10 +73,7 @@ where
                 view! {
                     <li class="flex-1 md:flex-auto">
                         <button
-                            on:click=move |_| {
-                                set_tab.set(i);
-                                on_tab_change();
-                            }
+                            on:click=move |_| set_tab.set(i)
                             class={move || format!("my-2 block w-full text-center
                                 border-b-2 py-1 md:py-4 ease font-body font-semibold
                                 text-xs md:text-xl leading-tight hover:text-blue-500 {active}",
@@ -1,15 +1,31 @@
+use std::time::Duration;
+
 use leptos::*;
 
 use super::{ErrorBlock, SubmitButton};
+use crate::client::InvoiceDescription;
 use crate::utils::empty_view;
 
+/// Expiry of invoices created without asking the user, e.g. for
+/// LNURL-withdraw
+pub const DEFAULT_INVOICE_EXPIRY: Duration = Duration::from_secs(60 * 60 * 24);
+
+const EXPIRY_OPTIONS: [(Duration, &str); 4] = [
+    (Duration::from_secs(60 * 10), "Expires in 10 minutes"),
+    (Duration::from_secs(60 * 60), "Expires in 1 hour"),
+    (DEFAULT_INVOICE_EXPIRY, "Expires in 1 day"),
+    (Duration::from_secs(60 * 60 * 24 * 7), "Expires in 1 week"),
+];
+
 #[component]
 pub fn LnReceiveForm<F>(on_submit: F) -> impl IntoView
 where
-    F: Fn(u64, String) + 'static + Copy,
+    F: Fn(u64, InvoiceDescription, Duration) + 'static + Copy,
 {
     let (amount, set_amount) = create_signal("".to_string());
     let (description, set_description) = create_signal("".to_string());
+    let (hash_description, set_hash_description) = create_signal(false);
+    let (expiry, set_expiry) = create_signal(DEFAULT_INVOICE_EXPIRY);
     let (error, set_error) = create_signal(None);
 
     let on_submit = move || {
@@ -24,7 +40,17 @@ where
             }
         };
 
-        on_submit(amount_msat, description.get());
+        let description = if hash_description.get() {
+            InvoiceDescription::Hash(description.get())
+        } else {
+            InvoiceDescription::Direct(description.get())
+        };
+        if let Err(e) = description.validate() {
+            set_error.set(Some(e.to_string()));
+            return;
+        }
+
+        on_submit(amount_msat, description, expiry.get());
     };
 
     view! {
@@ -63,6 +89,44 @@ where
                 }
                 prop:value=move || description.get()
             />
+            <label class="flex items-center gap-2 font-body text-gray-600">
+                <input
+                    type="checkbox"
+                    prop:checked=move || hash_description.get()
+                    on:change=move |ev| set_hash_description.set(event_target_checked(&ev))
+                />
+                "Only include the description's hash in the invoice (long descriptions, LNURL and zap metadata)"
+            </label>
+            <Show when=move || {
+                !hash_description.get() && description.get().len() > InvoiceDescription::MAX_DIRECT_LENGTH
+            }>
+                <p class="text-sm text-orange-700">
+                    {format!(
+                        "Descriptions longer than {} bytes only fit as a hash",
+                        InvoiceDescription::MAX_DIRECT_LENGTH
+                    )}
+                </p>
+            </Show>
+            <select
+                class="my-4 w-full font-body text-gray-600 border-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                on:change=move |ev| {
+                    if let Some((expiry, _)) = event_target_value(&ev)
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|idx| EXPIRY_OPTIONS.get(idx))
+                    {
+                        set_expiry.set(*expiry);
+                    }
+                }
+            >
+                {EXPIRY_OPTIONS.iter().enumerate().map(|(idx, (option, name))| {
+                    view! {
+                        <option value=idx.to_string() selected=*option == DEFAULT_INVOICE_EXPIRY>
+                            {*name}
+                        </option>
+                    }
+                }).collect_view()}
+            </select>
             <SubmitButton
                 class="my-4 w-full"
                 loading=create_signal(false).0
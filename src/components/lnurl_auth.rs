@@ -0,0 +1,75 @@
+use leptos::*;
+
+use super::{ErrorBlock, SubmitButton, SuccessBlock};
+use crate::context::ClientContext;
+use crate::lnurl::AuthRequest;
+
+//
+// LNURL-auth component
+// Asks the user to approve logging into a service with the wallet's linking
+// key for that service
+//
+#[component]
+pub fn LnurlAuth<F>(auth_request: AuthRequest, on_cancel: F) -> impl IntoView
+where
+    F: Fn() + 'static + Copy,
+{
+    let ClientContext { client, .. } = expect_context::<ClientContext>();
+
+    let domain = auth_request.domain.clone();
+    let action_description = auth_request.action_description();
+    let auth_request = store_value(auth_request);
+
+    let login_action = create_action(move |_: &()| {
+        let auth_request = auth_request.get_value();
+        async move {
+            let (key, sig) = client
+                .get_value()
+                .lnurl_auth_sign(auth_request.domain.clone(), auth_request.k1)
+                .await
+                .map_err(|e| e.to_string())?;
+            auth_request
+                .login(&key, &sig)
+                .await
+                .map_err(|e| format!("{e:#}"))
+        }
+    });
+    let done = move || matches!(login_action.value().get(), Some(Ok(())));
+
+    view! {
+        <div class="mt-8 font-body text-gray-600">
+            <h3 class="text-lg font-semibold mb-2">{format!("{action_description} {domain}?")}</h3>
+            <p class="text-sm mb-4">
+                "The wallet identifies itself with a key that is unique to this site, no funds are moved."
+            </p>
+            {move || match login_action.value().get() {
+                Some(Ok(())) => view! {
+                    <SuccessBlock>{format!("Logged in to {}", auth_request.with_value(|r| r.domain.clone()))}</SuccessBlock>
+                }
+                .into_view(),
+                Some(Err(e)) => view! { <ErrorBlock>{e}</ErrorBlock> }.into_view(),
+                None => ().into_view(),
+            }}
+            <Show when=move || !done()>
+                <div class="flex space-x-4 mt-4">
+                    <SubmitButton
+                        class="w-5/6"
+                        loading=login_action.pending()
+                        disabled=Signal::derive(move || login_action.pending().get())
+                        on_click=move |_| login_action.dispatch(())
+                    >
+                        "Approve"
+                    </SubmitButton>
+                    <SubmitButton
+                        class="w-1/6"
+                        loading=Signal::derive(|| false)
+                        disabled=Signal::derive(move || login_action.pending().get())
+                        on_click=move |_| on_cancel()
+                    >
+                        "Cancel"
+                    </SubmitButton>
+                </div>
+            </Show>
+        </div>
+    }
+}
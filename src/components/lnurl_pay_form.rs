@@ -0,0 +1,139 @@
+use leptos::*;
+
+use super::{ErrorBlock, SubmitButton};
+use crate::lnurl::PayRequest;
+use crate::utils::empty_view;
+
+//
+// LNURL-pay form component
+// Asks for the amount and comment of a payment to a LNURL-pay service or
+// Lightning Address
+//
+#[component]
+pub fn LnurlPayForm<F>(
+    pay_request: PayRequest,
+    on_submit: F,
+    #[prop(into)] loading: Signal<bool>,
+    /// Largest amount the balance can pay after fees, if known
+    #[prop(into)]
+    max_payable_msat: Signal<Option<u64>>,
+) -> impl IntoView
+where
+    F: Fn(u64, Option<String>) + 'static + Copy,
+{
+    let fixed_amount =
+        Some(pay_request.min_sendable).filter(|min| *min == pay_request.max_sendable);
+    let (amount, set_amount) = create_signal(
+        fixed_amount
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
+    );
+    let (comment, set_comment) = create_signal(String::new());
+    let (error, set_error) = create_signal(None);
+
+    let min_sendable = pay_request.min_sendable;
+    let max_sendable = pay_request.max_sendable;
+    let comment_allowed = pay_request.comment_allowed;
+
+    let set_max_amount = move || match max_payable_msat.get() {
+        Some(max_payable) if max_payable >= min_sendable => {
+            set_error.set(None);
+            set_amount.set(max_payable.min(max_sendable).to_string());
+        }
+        Some(_) => set_error.set(Some(format!(
+            "Your balance is too low to pay the minimum of {min_sendable} msat"
+        ))),
+        None => set_error.set(Some("Couldn't determine the balance available".to_owned())),
+    };
+
+    let on_submit = move || {
+        let amount_msat = match amount.get().parse::<u64>() {
+            Ok(amount) if (min_sendable..=max_sendable).contains(&amount) => amount,
+            Ok(_) => {
+                set_error.set(Some(format!(
+                    "The amount has to be between {min_sendable} and {max_sendable} msat"
+                )));
+                return;
+            }
+            Err(e) => {
+                set_error.set(Some(format!("Invalid amount: {e}")));
+                return;
+            }
+        };
+        set_error.set(None);
+
+        on_submit(amount_msat, Some(comment.get()).filter(|c| !c.is_empty()));
+    };
+
+    view! {
+        <form
+            class="mt-8 font-body text-gray-600"
+            on:submit=move |ev| {
+                ev.prevent_default();
+                on_submit()
+            }
+        >
+            <h3 class="text-lg font-semibold mb-2">{format!("Pay {}", pay_request.domain)}</h3>
+            {pay_request.description().map(|description| view! { <p class="mb-2">{description}</p> })}
+            <p class="text-sm text-gray-400">
+                {match fixed_amount {
+                    Some(amount) => format!("Amount: {amount} msat"),
+                    None => format!("Between {min_sendable} and {max_sendable} msat"),
+                }}
+            </p>
+            <div class="flex items-center gap-4">
+                <input
+                    type="number"
+                    placeholder="Amount msat"
+                    class="my-4 w-full font-body text-gray-600 border-gray-400 placeholder:text-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                    prop:disabled=fixed_amount.is_some()
+                    on:input=move |ev| {
+                        set_amount.set(event_target_value(&ev));
+                    }
+                    prop:value=move || amount.get()
+                />
+                <Show when=move || fixed_amount.is_none()>
+                    <button
+                        type="button"
+                        class="text-sm text-blue-500"
+                        title="Pay the entire balance minus the gateway fee"
+                        on:click=move |_| set_max_amount()
+                    >
+                        "Max"
+                    </button>
+                </Show>
+            </div>
+            <Show when=move || { comment_allowed > 0 }>
+                <input
+                    type="text"
+                    placeholder=format!("Comment (up to {comment_allowed} characters)")
+                    maxlength=comment_allowed.to_string()
+                    class="my-4 w-full font-body text-gray-600 border-gray-400 placeholder:text-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                    on:input=move |ev| {
+                        set_comment.set(event_target_value(&ev));
+                    }
+                    prop:value=move || comment.get()
+                />
+            </Show>
+            {move || {
+                if let Some(error) = error.get() {
+                    view!{
+                        <ErrorBlock class="mb-8">
+                            { error }
+                        </ErrorBlock>
+                    }.into_view()
+                } else {
+                    empty_view().into_view()
+                }
+            }}
+            <SubmitButton
+                class="my-4 w-full"
+                loading=loading
+                disabled=loading
+                on_click=move |_| {}
+            >
+                "Get invoice"
+            </SubmitButton>
+        </form>
+    }
+}
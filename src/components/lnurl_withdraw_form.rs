@@ -0,0 +1,133 @@
+use leptos::*;
+
+use super::{ErrorBlock, SubmitButton};
+use crate::lnurl::{self, WithdrawRequest};
+use crate::utils::empty_view;
+
+//
+// LNURL-withdraw form component
+// Loads a LNURL-withdraw code and asks how much of the offered amount to
+// withdraw
+//
+#[component]
+pub fn LnurlWithdrawForm<F>(on_submit: F, #[prop(into)] loading: Signal<bool>) -> impl IntoView
+where
+    F: Fn(u64, String, WithdrawRequest) + 'static + Copy,
+{
+    let (code, set_code) = create_signal(String::new());
+    let (amount, set_amount) = create_signal(String::new());
+    let (error, set_error) = create_signal(None);
+
+    let fetch_action = create_action(move |code: &String| {
+        let url = lnurl::parse(code);
+        async move {
+            let url = url.ok_or_else(|| "Not a LNURL-withdraw code".to_owned())?;
+            let withdraw_request = WithdrawRequest::fetch(url)
+                .await
+                .map_err(|e| format!("{e:#}"))?;
+            set_amount.set(withdraw_request.max_withdrawable.to_string());
+            Ok::<_, String>(withdraw_request)
+        }
+    });
+    let withdraw_request = move || fetch_action.value().get().and_then(Result::ok);
+
+    let on_withdraw = move || {
+        let Some(withdraw_request) = withdraw_request() else {
+            return;
+        };
+        let (min, max) = (
+            withdraw_request.min_withdrawable,
+            withdraw_request.max_withdrawable,
+        );
+        let amount_msat = match amount.get().parse::<u64>() {
+            Ok(amount) if (min..=max).contains(&amount) => amount,
+            Ok(_) => {
+                set_error.set(Some(format!(
+                    "The amount has to be between {min} and {max} msat"
+                )));
+                return;
+            }
+            Err(e) => {
+                set_error.set(Some(format!("Invalid amount: {e}")));
+                return;
+            }
+        };
+        set_error.set(None);
+
+        let description = withdraw_request.default_description.clone();
+        on_submit(amount_msat, description, withdraw_request);
+    };
+
+    view! {
+        <form
+            class="mt-8 font-body text-gray-600"
+            on:submit=move |ev| {
+                ev.prevent_default();
+                if withdraw_request().is_some() {
+                    on_withdraw();
+                } else {
+                    fetch_action.dispatch(code.get());
+                }
+            }
+        >
+            <p class="text-xl">"Or withdraw from a LNURL-withdraw code"</p>
+            <input
+                type="text"
+                placeholder="lnurl1…"
+                class="my-4 w-full font-body text-gray-600 border-gray-400 placeholder:text-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                on:input=move |ev| {
+                    set_code.set(event_target_value(&ev));
+                    fetch_action.value().set(None);
+                }
+                prop:value=move || code.get()
+            />
+            {move || {
+                withdraw_request().map(|withdraw_request| {
+                    view! {
+                        <p class="mb-2">
+                            {format!(
+                                "{} offers between {} and {} msat",
+                                withdraw_request.domain,
+                                withdraw_request.min_withdrawable,
+                                withdraw_request.max_withdrawable,
+                            )}
+                        </p>
+                        <input
+                            type="number"
+                            placeholder="Amount msat"
+                            class="my-4 w-full font-body text-gray-600 border-gray-400 placeholder:text-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                            on:input=move |ev| {
+                                set_amount.set(event_target_value(&ev));
+                            }
+                            prop:value=move || amount.get()
+                        />
+                    }
+                })
+            }}
+            {move || {
+                let error = error
+                    .get()
+                    .or_else(|| fetch_action.value().get().and_then(Result::err));
+                if let Some(error) = error {
+                    view!{
+                        <ErrorBlock class="mb-8">
+                            { error }
+                        </ErrorBlock>
+                    }.into_view()
+                } else {
+                    empty_view().into_view()
+                }
+            }}
+            <SubmitButton
+                class="my-4 w-full"
+                loading=Signal::derive(move || loading.get() || fetch_action.pending().get())
+                disabled=Signal::derive(move || {
+                    loading.get() || fetch_action.pending().get() || code.get().is_empty()
+                })
+                on_click=move |_| {}
+            >
+                {move || if withdraw_request().is_some() { "Withdraw" } else { "Load code" }}
+            </SubmitButton>
+        </form>
+    }
+}
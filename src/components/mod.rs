@@ -1,14 +1,20 @@
 pub mod alerts;
 pub mod app;
 pub mod balance;
+pub mod balance_history;
 pub mod copyable_text;
 pub mod create_wallet;
 pub mod footer;
+pub mod gateway_selector;
 pub mod joined;
 pub mod ln_receive_form;
+pub mod lnurl_auth;
+pub mod lnurl_pay_form;
+pub mod lnurl_withdraw_form;
 pub mod loader_icon;
 pub mod logo;
 pub mod logo_fedimint;
+pub mod note_holdings;
 pub mod protocol_selector;
 pub mod qrcode;
 pub mod receive;
@@ -19,6 +25,7 @@ pub mod send;
 pub mod send_ecash;
 pub mod send_ln;
 pub mod service_worker;
+pub mod settings;
 pub mod submit_button;
 pub mod submit_form;
 pub mod tx_list;
@@ -27,12 +34,17 @@ pub mod wallet_selector;
 pub use alerts::*;
 pub use app::*;
 pub use balance::*;
+pub use balance_history::*;
 pub use copyable_text::*;
 pub use footer::*;
+pub use gateway_selector::*;
 pub use joined::*;
+pub use lnurl_auth::*;
+pub use lnurl_pay_form::*;
 pub use loader_icon::*;
 pub use logo::*;
 pub use logo_fedimint::*;
+pub use note_holdings::*;
 pub use protocol_selector::*;
 pub use qrcode::*;
 pub use receive::*;
@@ -42,6 +54,7 @@ pub use segmented_button::*;
 pub use send::*;
 pub use send_ecash::*;
 pub use send_ln::*;
+pub use settings::*;
 pub use submit_button::*;
 pub use submit_form::*;
 pub use tx_list::*;
@@ -0,0 +1,163 @@
+use fedimint_core::Amount;
+use leptos::*;
+
+use crate::components::{ErrorBlock, LoaderIcon};
+use crate::context::ClientContext;
+
+//
+// Note holdings component
+// How the balance is split across note denominations
+//
+#[component]
+pub fn NoteHoldings(
+    /// Notes change with every change of the balance
+    #[prop(into)]
+    balance: Signal<Option<Amount>>,
+    /// Whether a manual or automatic consolidation is running
+    #[prop(into)]
+    consolidating: Signal<bool>,
+    #[prop(optional, into)] class: String,
+) -> impl IntoView {
+    let ClientContext { client, .. } = expect_context::<ClientContext>();
+
+    let consolidate_action =
+        create_action(move |_: &()| async move { client.get_value().consolidate_notes().await });
+
+    // Reload whenever notes were spent, received or consolidated
+    let holdings_resource = create_resource(
+        move || (balance.get(), consolidate_action.version().get()),
+        move |_| async move { client.get_value().note_holdings().await },
+    );
+
+    let holdings = move || {
+        holdings_resource.get().map(|holdings| match holdings {
+            Ok(holdings) => view! { <NoteTable holdings=holdings /> }.into_view(),
+            Err(e) => view! {
+                <ErrorBlock>{format!("Failed to load notes: {e}")}</ErrorBlock>
+            }
+            .into_view(),
+        })
+    };
+
+    view! {
+        <div class=class>
+            <Suspense fallback=move || view! { <LoaderIcon /> }>
+                {holdings}
+            </Suspense>
+            <button
+                class="mt-2 text-sm text-blue-500 disabled:text-gray-400"
+                prop:disabled=move || consolidating.get() || consolidate_action.pending().get()
+                on:click=move |_| consolidate_action.dispatch(())
+            >
+                {move || {
+                    if consolidating.get() || consolidate_action.pending().get() {
+                        "Consolidating…"
+                    } else {
+                        "Consolidate notes"
+                    }
+                }}
+            </button>
+            {move || {
+                consolidate_action.value().get().map(|result| {
+                    let message = match result {
+                        Ok(consolidation) => format!(
+                            "Merged {} notes, the wallet now holds {} instead of {}",
+                            consolidation.merged(),
+                            consolidation.notes_after,
+                            consolidation.notes_before
+                        ),
+                        Err(e) => format!("Failed to consolidate notes: {e}"),
+                    };
+                    view! { <p class="text-sm text-gray-600">{message}</p> }
+                })
+            }}
+        </div>
+    }
+}
+
+#[component]
+fn NoteTable(holdings: Vec<(Amount, usize)>) -> impl IntoView {
+    let note_count = holdings.iter().map(|(_, count)| count).sum::<usize>();
+    let exact_limit = exact_amount_limit(&holdings);
+
+    view! {
+        <table class="w-full font-body text-gray-600">
+            <thead>
+                <tr class="text-left text-gray-400 border-b border-slate-300">
+                    <th class="py-1">"Denomination"</th>
+                    <th class="py-1 text-right">"Notes"</th>
+                    <th class="py-1 text-right">"Total"</th>
+                </tr>
+            </thead>
+            <tbody>
+                {holdings.into_iter().rev().map(|(denomination, count)| view! {
+                    <tr class="border-b border-slate-200">
+                        <td class="py-1">{format!("{} msat", denomination.msats)}</td>
+                        <td class="py-1 text-right">{count}</td>
+                        <td class="py-1 text-right">{format!("{} msat", denomination.msats * count as u64)}</td>
+                    </tr>
+                }).collect_view()}
+            </tbody>
+        </table>
+        <p class="mt-2 text-sm text-gray-400">
+            {format!(
+                "{note_count} notes in total. Any amount up to {} msat can be sent exactly without exchanging notes first.",
+                exact_limit.msats
+            )}
+        </p>
+    }
+}
+
+/// Largest amount such that every amount up to it can be made up exactly from
+/// the notes
+fn exact_amount_limit(holdings: &[(Amount, usize)]) -> Amount {
+    let mut limit = Amount::ZERO;
+    for (denomination, count) in holdings {
+        for _ in 0..*count {
+            if denomination.msats > limit.msats + 1 {
+                return limit;
+            }
+            limit += *denomination;
+        }
+    }
+    limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holdings(notes: &[(u64, usize)]) -> Vec<(Amount, usize)> {
+        notes
+            .iter()
+            .map(|(denomination, count)| (Amount::from_msats(*denomination), *count))
+            .collect()
+    }
+
+    #[test]
+    fn exact_amount_limit_without_gaps() {
+        assert_eq!(exact_amount_limit(&[]), Amount::ZERO);
+        assert_eq!(
+            exact_amount_limit(&holdings(&[(1, 1), (2, 1), (4, 1)])),
+            Amount::from_msats(7)
+        );
+        assert_eq!(
+            exact_amount_limit(&holdings(&[(1, 2), (2, 1), (4, 3)])),
+            Amount::from_msats(16)
+        );
+    }
+
+    #[test]
+    fn exact_amount_limit_stops_at_gaps() {
+        // 2 msat can't be made up from a 1 and a 4 msat note
+        assert_eq!(
+            exact_amount_limit(&holdings(&[(1, 1), (4, 1)])),
+            Amount::from_msats(1)
+        );
+        assert_eq!(
+            exact_amount_limit(&holdings(&[(1, 2), (2, 1), (8, 1)])),
+            Amount::from_msats(4)
+        );
+        assert_eq!(exact_amount_limit(&holdings(&[(2, 3)])), Amount::ZERO);
+    }
+}
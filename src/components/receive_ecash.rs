@@ -1,7 +1,12 @@
+use fedimint_core::core::OperationId;
+use futures::StreamExt;
 use leptos::*;
 
+use super::{SubmitButton, WarningBlock};
+use crate::client::{EcashPreview, EcashReceiveProgress, RpcError};
 use crate::components::SubmitForm;
 use crate::context::ClientContext;
+use crate::utils::PendingRedemption;
 
 //
 // Receive e-cash component
@@ -10,30 +15,107 @@ use crate::context::ClientContext;
 pub fn ReceiveEcash() -> impl IntoView {
     let ClientContext { client, .. } = expect_context::<ClientContext>();
 
+    let (preview, set_preview) = create_signal(None::<EcashPreview>);
+    let (receive_state, set_receive_state) = create_signal(None::<EcashReceiveProgress>);
+    // Updates of earlier redemptions must not overwrite the state of the one
+    // shown
+    let active_operation = store_value(None::<OperationId>);
+
     let client = client.clone();
-    let submit_action = create_action(move |invoice: &String| {
-        let invoice = invoice.clone();
-        async move { client.get_value().ecash_receive(invoice).await }
+    // Notes are only redeemed once the user confirmed the decoded notes
+    let preview_action = create_action(move |notes: &String| {
+        let notes = notes.clone();
+        set_preview.set(None);
+        async move {
+            let preview = client.get_value().preview_ecash(notes).await?;
+            set_preview.set(Some(preview));
+            Ok::<_, RpcError>(())
+        }
+    });
+
+    // The notes are only ours once the federation reissued them, which could
+    // still fail if they were spent already
+    let submit_action = create_action(move |notes: &String| {
+        let notes = notes.clone();
+        set_preview.set(None);
+        set_receive_state.set(None);
+        active_operation.set_value(None);
+        async move {
+            let (redemption, mut updates) = client.get_value().ecash_receive(notes).await?;
+            let operation_id = redemption.operation_id;
+            active_operation.set_value(Some(operation_id));
+            spawn_local(async move {
+                while let Some(state) = updates.next().await {
+                    if active_operation.try_get_value() != Some(Some(operation_id)) {
+                        break;
+                    }
+                    let is_final = state.is_final();
+                    set_receive_state.set(Some(state));
+                    if is_final {
+                        break;
+                    }
+                }
+            });
+            Ok::<_, RpcError>(redemption)
+        }
+    });
+
+    // Notes of other federations are redeemed into a new wallet that joins the
+    // federation of the invite code embedded in the notes, which reloads the app
+    let join_action = create_action(move |pending: &PendingRedemption| {
+        let result = pending.start().map_err(|e| format!("{e:#}"));
+        async move { result }
     });
 
     view! {
 
       <SubmitForm
         description="Enter e-cash notes (i.e. BAQB6ijaAs0mXNoyKYvhI…) to redeem".into()
-        on_submit=move |v| submit_action.dispatch(v)
+        on_submit=move |v| preview_action.dispatch(v)
         placeholder="e-cash notes".into()
-        submit_label="Redeem".into()
-        loading=submit_action.pending()
+        submit_label="Check".into()
+        loading=Signal::derive(move || {
+          preview_action.pending().get() || submit_action.pending().get() || join_action.pending().get()
+        })
         default_scan=true
       />
 
+      {move ||
+        preview_action.value().get().and_then(|result| result.err()).map(|error| view!(
+          <div class="text-body mt-4">
+            <span class="text-red-500">{format!("✗ Invalid e-cash notes: {error}")}</span>
+          </div>
+        ))
+      }
+
+      {move ||
+        preview.get().map(|preview| view!(
+          <EcashPreviewCard
+            preview=preview
+            on_confirm=move |notes| submit_action.dispatch(notes)
+            on_join=move |pending| join_action.dispatch(pending)
+            on_cancel=move || set_preview.set(None)
+          />
+        ))
+      }
+
+      {move ||
+        join_action.value().get().and_then(|result| result.err()).map(|error| view!(
+          <div class="text-body mt-4">
+            <span class="text-red-500">{format!("✗ Failed to create the wallet: {error}")}</span>
+          </div>
+        ))
+      }
+
       {move ||
         if let Some(result) = submit_action.value().get() {
           view!(
             <div class="text-body mt-4">{
               match result {
                 Err(error) => view!(<span class="text-red-500">{format!("✗ Failed to redeem e-cash: {error}")}</span>),
-                Ok(value) => view!(<span class="text-green-600">{format!("✓ Redeemed {:?} msat", value.msats)}</span>)
+                Ok(redemption) => view!(
+                  <span><RedemptionStatus amount_msat=redemption.amount.msats state=receive_state /></span>
+                ),
               }
             }
             </div>)
@@ -44,3 +126,170 @@ pub fn ReceiveEcash() -> impl IntoView {
 
     }
 }
+
+#[component]
+fn EcashPreviewCard<C, J, X>(
+    preview: EcashPreview,
+    on_confirm: C,
+    on_join: J,
+    on_cancel: X,
+) -> impl IntoView
+where
+    C: Fn(String) + 'static + Copy,
+    J: Fn(PendingRedemption) + 'static + Copy,
+    X: Fn() + 'static + Copy,
+{
+    let row = |label: &'static str, value: String| {
+        view! {
+            <div class="flex justify-between gap-4 py-1 border-b border-gray-200">
+                <span class="text-gray-400">{label}</span>
+                <span class="text-right break-all">{value}</span>
+            </div>
+        }
+    };
+
+    let same_federation = preview.same_federation;
+    let notes = store_value(preview.notes.clone());
+    let (wallet_name, set_wallet_name) = create_signal(String::new());
+    let joinable = !same_federation && preview.federation_invite.is_some();
+
+    view! {
+        <div class="mt-8 font-body text-gray-600">
+            <h3 class="text-lg font-semibold mb-2">"Review e-cash"</h3>
+            {row("Amount", format!("{} msat", preview.total_amount.msats))}
+            {row("Federation", preview.federation_id_prefix.clone())}
+            <div class="py-1 border-b border-gray-200">
+                <span class="text-gray-400">"Denominations"</span>
+                <ul class="text-right">
+                    {preview.denominations.iter().map(|(denomination, count)| {
+                        view! { <li>{format!("{count} × {} msat", denomination.msats)}</li> }
+                    }).collect_view()}
+                </ul>
+            </div>
+
+            <Show when=move || !same_federation>
+                <WarningBlock class="mt-4">
+                    "These notes were issued by a different federation than the one of this wallet and can't be redeemed here."
+                </WarningBlock>
+            </Show>
+            {preview.federation_invite.clone().filter(|_| joinable).map(|invite| view! {
+                <p class="mt-4">
+                    "The notes include an invite code of their federation, you can redeem them into a new wallet that joins it. The app reloads to open the new wallet:"
+                </p>
+                <p class="text-sm text-gray-400 break-all">{invite.clone()}</p>
+                <input
+                    type="text"
+                    placeholder="Name of the new wallet"
+                    class="my-2 w-full font-body text-gray-600 border-gray-400 placeholder:text-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                    on:input=move |ev| set_wallet_name.set(event_target_value(&ev))
+                    prop:value=move || wallet_name.get()
+                />
+                <SubmitButton
+                    class="w-full"
+                    loading=Signal::derive(|| false)
+                    disabled=Signal::derive(move || wallet_name.get().trim().is_empty())
+                    on_click=move |_| {
+                        on_join(PendingRedemption {
+                            wallet_name: wallet_name.get().trim().to_owned(),
+                            invite_code: invite.clone(),
+                            notes: notes.get_value(),
+                        })
+                    }
+                >
+                    "Join and redeem"
+                </SubmitButton>
+            })}
+
+            <div class="flex space-x-4 mt-4">
+                <SubmitButton
+                    class="w-5/6"
+                    loading=Signal::derive(|| false)
+                    disabled=Signal::derive(move || !same_federation)
+                    on_click=move |_| on_confirm(notes.get_value())
+                >
+                    "Redeem"
+                </SubmitButton>
+                <SubmitButton
+                    class="w-1/6"
+                    loading=Signal::derive(|| false)
+                    disabled=Signal::derive(|| false)
+                    on_click=move |_| on_cancel()
+                >
+                    "Cancel"
+                </SubmitButton>
+            </div>
+        </div>
+    }
+}
+
+/// Redeems `notes` as soon as it's shown, e.g. notes of another federation
+/// once the app reloaded into the new wallet that joined it
+#[component]
+pub fn RedeemEcash(notes: String) -> impl IntoView {
+    let ClientContext { client, .. } = expect_context::<ClientContext>();
+
+    let (receive_state, set_receive_state) = create_signal(None::<EcashReceiveProgress>);
+
+    let redeem_action = create_action(move |notes: &String| {
+        let notes = notes.clone();
+        async move {
+            let (redemption, mut updates) = client.get_value().ecash_receive(notes).await?;
+            // The wallet is reissuing the notes now, so they don't have to be
+            // kept for another attempt
+            PendingRedemption::clear();
+            spawn_local(async move {
+                while let Some(state) = updates.next().await {
+                    let is_final = state.is_final();
+                    set_receive_state.set(Some(state));
+                    if is_final {
+                        break;
+                    }
+                }
+            });
+            Ok::<_, RpcError>(redemption)
+        }
+    });
+    redeem_action.dispatch(notes);
+
+    move || {
+        redeem_action.value().get().map(|result| {
+            view! {
+                <div class="text-body mb-4">
+                    {match result {
+                        Ok(redemption) => view! {
+                            <RedemptionStatus amount_msat=redemption.amount.msats state=receive_state />
+                        }
+                        .into_view(),
+                        Err(error) => view! {
+                            <span class="text-red-500">{format!("✗ Failed to redeem e-cash: {error}")}</span>
+                        }
+                        .into_view(),
+                    }}
+                </div>
+            }
+        })
+    }
+}
+
+#[component]
+fn RedemptionStatus(
+    amount_msat: u64,
+    state: ReadSignal<Option<EcashReceiveProgress>>,
+) -> impl IntoView {
+    move || match state.get() {
+        Some(EcashReceiveProgress::Done) => view! {
+            <span class="text-green-600">{format!("✓ Redeemed {amount_msat} msat")}</span>
+        },
+        Some(EcashReceiveProgress::Failed { reason }) => view! {
+            <span class="text-red-500">{format!("✗ Failed to redeem e-cash: {reason}")}</span>
+        },
+        Some(EcashReceiveProgress::Issuing) => view! {
+            <span class="text-gray-600">
+                {format!("Redeeming {amount_msat} msat, waiting for the federation…")}
+            </span>
+        },
+        None | Some(EcashReceiveProgress::Created) => view! {
+            <span class="text-gray-600">{format!("Redeeming {amount_msat} msat…")}</span>
+        },
+    }
+}
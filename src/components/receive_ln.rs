@@ -1,11 +1,19 @@
+use std::time::{Duration, SystemTime};
+
+use fedimint_core::core::OperationId;
+use futures::StreamExt;
 use leptos::*;
 
-use super::{CopyableText, ErrorBlock, SuccessBlock};
-use crate::components::ln_receive_form::LnReceiveForm;
+use super::{CopyableText, ErrorBlock, SuccessBlock, WarningBlock};
+use crate::client::{InvoiceDescription, LnReceiveProgress};
+use crate::components::gateway_selector::GatewaySelector;
+use crate::components::ln_receive_form::{LnReceiveForm, DEFAULT_INVOICE_EXPIRY};
+use crate::components::lnurl_withdraw_form::LnurlWithdrawForm;
 use crate::components::loader_icon::LoaderIcon;
 use crate::components::qrcode::QrCode;
 use crate::context::ClientContext;
-use crate::utils::empty_view;
+use crate::lnurl::WithdrawRequest;
+use crate::utils::{create_clock, empty_view, format_duration};
 
 //
 // Receive LN component
@@ -14,24 +22,80 @@ use crate::utils::empty_view;
 pub fn ReceiveLn() -> impl IntoView {
     let ClientContext { client, .. } = expect_context::<ClientContext>();
 
+    let (gateway_id, set_gateway_id) = create_signal(None);
+    let (receive_state, set_receive_state) = create_signal(None::<LnReceiveProgress>);
+    // Updates of previously created invoices must not overwrite the state of
+    // the one shown
+    let active_operation = store_value(None::<OperationId>);
+
     let client = client.clone();
-    let submit_action = create_action(move |(amount_msat, description): &(u64, String)| {
-        let description = description.clone();
-        let amount_msat = *amount_msat;
-        async move {
-            client
-                .get_value()
-                .ln_receive(amount_msat, description)
-                .await
-        }
-    });
+    // For LNURL-withdraw the invoice is handed to the service, which then pays
+    // it like any other invoice
+    let submit_action = create_action(
+        move |(amount_msat, description, expiry, withdraw_request): &(
+            u64,
+            InvoiceDescription,
+            Duration,
+            Option<WithdrawRequest>,
+        )| {
+            let description = description.clone();
+            let amount_msat = *amount_msat;
+            let expiry = *expiry;
+            let withdraw_request = withdraw_request.clone();
+            let gateway_id = gateway_id.get_untracked();
+            set_receive_state.set(None);
+            active_operation.set_value(None);
+            async move {
+                let (invoice, mut updates) = client
+                    .get_value()
+                    .ln_receive(amount_msat, description, expiry, gateway_id)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let operation_id = invoice.operation_id;
+                active_operation.set_value(Some(operation_id));
+                spawn_local(async move {
+                    while let Some(state) = updates.next().await {
+                        if active_operation.try_get_value() != Some(Some(operation_id)) {
+                            break;
+                        }
+                        let is_final = state.is_final();
+                        set_receive_state.set(Some(state));
+                        if is_final {
+                            break;
+                        }
+                    }
+                });
+                if let Some(withdraw_request) = withdraw_request {
+                    withdraw_request
+                        .submit_invoice(&invoice.invoice)
+                        .await
+                        .map_err(|e| format!("Failed to withdraw: {e:#}"))?;
+                }
+                Ok::<_, String>(invoice)
+            }
+        },
+    );
 
     view! {
+        <GatewaySelector
+            on_change=move |gateway_id| set_gateway_id.set(gateway_id)
+        />
         <LnReceiveForm
-            on_submit=move |amount_msat, description| {
-                submit_action.dispatch((amount_msat, description));
+            on_submit=move |amount_msat, description, expiry| {
+                submit_action.dispatch((amount_msat, description, expiry, None));
             }
         />
+        <LnurlWithdrawForm
+            on_submit=move |amount_msat, description, withdraw_request| {
+                submit_action.dispatch((
+                    amount_msat,
+                    InvoiceDescription::Direct(description),
+                    DEFAULT_INVOICE_EXPIRY,
+                    Some(withdraw_request),
+                ));
+            }
+            loading=submit_action.pending()
+        />
         <div class="w-full my-4 p-4 bg-slate-100 flex justify-center">
             <Show
                 when=move || !submit_action.pending().get()
@@ -39,34 +103,16 @@ pub fn ReceiveLn() -> impl IntoView {
             >
             { move || {
                 match submit_action.value().get() {
-                    Some(Ok((invoice, await_paid))) => {
+                    Some(Ok(receive_invoice)) => {
+                        let invoice = receive_invoice.invoice;
                         let qr_invoice_upper = format!("lightning:{invoice}").to_ascii_uppercase();
 
-                        let paid_resource = create_resource(|| (), move |()| {
-                            let mut await_paid = await_paid.clone();
-                            async move {
-                                let _ = await_paid.wait_for(|paid| *paid).await;
-                            }
-                        });
-
                         view!{
                             <div class="w-full">
-                                { move || {
-                                    // TODO: fix
-                                    // Needed to suppress loading screen
-                                    if paid_resource.loading().get() {
-                                        return empty_view().into_view();
-                                    }
-
-                                    match paid_resource.get() {
-                                        Some(()) => view! {
-                                            <SuccessBlock class="mb-8">
-                                                "The invoice has been paid!"
-                                            </SuccessBlock>
-                                        }.into_view(),
-                                        None => empty_view().into_view(),
-                                    }
-                                }}
+                                <ReceiveStatus
+                                    state=receive_state
+                                    expires_at=receive_invoice.expires_at
+                                />
                                 <CopyableText
                                     text={ Signal::derive(move || invoice.clone()) }
                                     rows=9
@@ -81,7 +127,7 @@ pub fn ReceiveLn() -> impl IntoView {
                     Some(Err(e)) => {
                         view!{
                             <ErrorBlock class="mb-8">
-                                { e.to_string() }
+                                { e }
                             </ErrorBlock>
                         }.into_view()
                     }
@@ -94,3 +140,61 @@ pub fn ReceiveLn() -> impl IntoView {
         </div>
     }
 }
+
+#[component]
+fn ReceiveStatus(
+    state: ReadSignal<Option<LnReceiveProgress>>,
+    expires_at: SystemTime,
+) -> impl IntoView {
+    let clock = create_clock();
+    let remaining_time = move || expires_at.duration_since(clock.get()).ok();
+
+    move || {
+        let state = state.get();
+        let paid = state.as_ref().is_some_and(LnReceiveProgress::is_paid);
+        if remaining_time().is_none() && !paid {
+            return view! {
+                <WarningBlock class="mb-8">
+                    "The invoice has expired without being paid, create a new one."
+                </WarningBlock>
+            }
+            .into_view();
+        }
+
+        let status = match state {
+            None | Some(LnReceiveProgress::Created) => "Creating the invoice…",
+            Some(LnReceiveProgress::WaitingForPayment) => "Waiting for payment",
+            Some(LnReceiveProgress::Funded) => "Paid, the gateway funded the payment",
+            Some(LnReceiveProgress::AwaitingFunds) => "Paid, waiting for the e-cash to be issued",
+            Some(LnReceiveProgress::Claimed) => {
+                return view! {
+                    <SuccessBlock class="mb-8">
+                        "The invoice has been paid!"
+                    </SuccessBlock>
+                }
+                .into_view();
+            }
+            Some(LnReceiveProgress::Canceled { reason }) => {
+                return view! {
+                    <ErrorBlock class="mb-8">
+                        {format!("The invoice was canceled: {reason}")}
+                    </ErrorBlock>
+                }
+                .into_view();
+            }
+        };
+
+        view! {
+            <p class="mb-4 font-body text-gray-600">
+                {status}
+                <Show when=move || !paid>
+                    {move || {
+                        remaining_time()
+                            .map(|remaining| format!(" · expires in {}", format_duration(remaining)))
+                    }}
+                </Show>
+            </p>
+        }
+        .into_view()
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use fedimint_core::Amount;
 use fedimint_mint_client::OOBNotes;
 use leptos::*;
@@ -6,6 +8,16 @@ use super::{CopyableText, ErrorBlock, QrCode, SubmitButton, SuccessBlock, Warnin
 use crate::client::RpcError;
 use crate::context::ClientContext;
 
+/// Choices for the time after which unredeemed ecash spends are reclaimed
+pub const RECLAIM_TIMEOUT_OPTIONS: [(Duration, &str); 6] = [
+    (Duration::from_secs(60 * 10), "10 minutes"),
+    (Duration::from_secs(60 * 60), "1 hour"),
+    (Duration::from_secs(60 * 60 * 24), "1 day"),
+    (Duration::from_secs(60 * 60 * 24 * 3), "3 days"),
+    (Duration::from_secs(60 * 60 * 24 * 7), "1 week"),
+    (Duration::from_secs(60 * 60 * 24 * 28), "4 weeks"),
+];
+
 //
 // Send Ecash component
 //
@@ -14,36 +26,67 @@ pub fn SendEcash() -> impl IntoView {
     let ClientContext { client, .. } = expect_context::<ClientContext>();
 
     let (amount, set_amount) = create_signal("".to_owned());
+    let (reclaim_after, set_reclaim_after) = create_signal(None::<Duration>);
+    let (exact, set_exact) = create_signal(false);
+    let (offline, set_offline) = create_signal(false);
+    let (include_invite, set_include_invite) = create_signal(false);
+    let (send_all, set_send_all) = create_signal(false);
     let (error, set_error) = create_signal(None);
 
+    #[derive(Clone, Copy)]
+    struct SendRequest {
+        /// `None` sends the entire balance
+        amount: Option<Amount>,
+        reclaim_after: Option<Duration>,
+        exact: bool,
+        include_invite: bool,
+    }
     #[derive(Clone)]
     struct ActionReturn {
-        requested_amount: Amount,
+        request: SendRequest,
         result: anyhow::Result<OOBNotes, RpcError>,
     }
     let client = client.clone();
-    let submit_action = create_action(move |amount: &Amount| {
-        let amount = amount.to_owned();
+    let submit_action = create_action(move |request: &SendRequest| {
+        let request = *request;
         async move {
             ActionReturn {
-                requested_amount: amount,
-                result: client.get_value().ecash_send(amount).await,
+                request,
+                result: client
+                    .get_value()
+                    .ecash_send(
+                        request.amount,
+                        request.reclaim_after,
+                        request.exact,
+                        request.include_invite,
+                    )
+                    .await,
             }
         }
     });
 
     let parse_and_submit = move || {
-        let amount = match amount.get().parse::<Amount>() {
-            Ok(a) => a,
-            Err(e) => {
-                set_error.set(Some(format!("Invalid amount: {e}")));
-                return;
+        let amount = if send_all.get() {
+            None
+        } else {
+            match amount.get().parse::<Amount>() {
+                Ok(a) => Some(a),
+                Err(e) => {
+                    set_error.set(Some(format!("Invalid amount: {e}")));
+                    return;
+                }
             }
         };
 
         set_error.set(None);
 
-        submit_action.dispatch(amount);
+        submit_action.dispatch(SendRequest {
+            amount,
+            reclaim_after: reclaim_after.get(),
+            // Exchanging notes needs the federation
+            exact: exact.get() && !offline.get(),
+            include_invite: include_invite.get(),
+        });
     };
 
     view! {
@@ -56,7 +99,77 @@ pub fn SendEcash() -> impl IntoView {
                     set_amount.set(event_target_value(&ev));
                 }
                 prop:value=move || amount.get()
+                prop:disabled=move || send_all.get()
             />
+            <label class="flex items-center gap-2 font-body text-gray-600">
+                <input
+                    type="checkbox"
+                    prop:checked=move || send_all.get()
+                    on:change=move |ev| set_send_all.set(event_target_checked(&ev))
+                />
+                "Send the entire balance"
+            </label>
+
+            <select
+                class="w-full font-body text-gray-600 border-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                on:change=move |ev| {
+                    set_reclaim_after.set(
+                        event_target_value(&ev)
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|idx| RECLAIM_TIMEOUT_OPTIONS.get(idx))
+                            .map(|(timeout, _)| *timeout),
+                    );
+                }
+            >
+                <option value="" selected=true>"Reclaim if not redeemed: wallet default"</option>
+                {RECLAIM_TIMEOUT_OPTIONS.iter().enumerate().map(|(idx, (_, name))| {
+                    view! {
+                        <option value=idx.to_string()>
+                            {format!("Reclaim if not redeemed within {name}")}
+                        </option>
+                    }
+                }).collect_view()}
+            </select>
+
+            <label class="flex items-center gap-2 font-body text-gray-600">
+                <input
+                    type="checkbox"
+                    prop:checked=move || offline.get()
+                    on:change=move |ev| set_offline.set(event_target_checked(&ev))
+                />
+                "Offline send"
+            </label>
+            <Show when=move || offline.get()>
+                <WarningBlock>
+                    "Only notes already held by this wallet are used and the federation isn't contacted. "
+                    "The recipient can't check the notes until they are online, so until they redeem them "
+                    "the notes could still be spent elsewhere, for example when this wallet reclaims them. "
+                    "Only hand out offline e-cash to people who trust you."
+                </WarningBlock>
+            </Show>
+            <label class="flex items-center gap-2 font-body text-gray-600">
+                <input
+                    type="checkbox"
+                    prop:checked=move || include_invite.get()
+                    on:change=move |ev| set_include_invite.set(event_target_checked(&ev))
+                />
+                "Include the federation's invite code so the recipient can join it"
+            </label>
+            <label class="flex items-center gap-2 font-body text-gray-600">
+                <input
+                    type="checkbox"
+                    prop:checked=move || exact.get() && !offline.get()
+                    prop:disabled=move || offline.get() || send_all.get()
+                    on:change=move |ev| set_exact.set(event_target_checked(&ev))
+                />
+                "Send the exact amount"
+            </label>
+            <Show when=move || exact.get() && !offline.get()>
+                <p class="text-sm text-gray-400">
+                    "If your notes don't add up to the amount, they are first exchanged with the federation for smaller ones. This takes a few seconds."
+                </p>
+            </Show>
 
             <SubmitButton
                 loading=submit_action.pending()
@@ -79,12 +192,31 @@ pub fn SendEcash() -> impl IntoView {
 
             {move || {
                 submit_action.value().get().map(|r| {
-                    r.result.err().map(|err| {
-                        view! {
+                    r.result.err().map(|err| match err {
+                        RpcError::FederationUnreachable if r.request.exact => {
+                            let request = SendRequest { exact: false, ..r.request };
+                            view! {
+                                <WarningBlock class="mb-8">
+                                    "The federation can't be reached to exchange your notes for the exact amount. "
+                                    "You can still send notes that are worth more than the amount, the recipient keeps the difference."
+                                </WarningBlock>
+                                <SubmitButton
+                                    loading=submit_action.pending()
+                                    disabled=submit_action.pending().into()
+                                    on_click=move |_| submit_action.dispatch(request)
+                                    class="w-full"
+                                >
+                                    "Send with overpayment"
+                                </SubmitButton>
+                            }
+                            .into_view()
+                        }
+                        err => view! {
                             <ErrorBlock class="mb-8">
                                 {format!("{:?}", err)}
                             </ErrorBlock>
                         }
+                        .into_view(),
                     })
                 })
             }}
@@ -98,9 +230,9 @@ pub fn SendEcash() -> impl IntoView {
                             <SuccessBlock class="mb-8">
                                 {format!("Notes representing {} shown below.", total)}
                             </SuccessBlock>
-                            <Show when=move || total != r.requested_amount>
+                            <Show when=move || r.request.amount.is_some_and(|amount| total != amount)>
                                 <WarningBlock>
-                                    {format!("The notes represent {} more than the amount you requested.", total-r.requested_amount)}
+                                    {format!("The notes represent {} more than the amount you requested.", total - r.request.amount.unwrap_or(total))}
                                 </WarningBlock>
                             </Show>
                             <CopyableText
@@ -1,7 +1,15 @@
+use fedimint_core::core::OperationId;
+use futures::StreamExt;
 use leptos::*;
 
-use crate::components::SubmitForm;
+use crate::client::{InvoicePreview, LnPaymentState, RpcError};
+use crate::components::{
+    ErrorBlock, GatewaySelector, LnurlAuth, LnurlPayForm, LoaderIcon, SubmitButton, SubmitForm,
+    SuccessBlock, WarningBlock,
+};
 use crate::context::ClientContext;
+use crate::lnurl::{self, AuthRequest, PayRequest, SuccessAction};
+use crate::utils::{create_clock, format_duration};
 
 //
 // Send LN component
@@ -10,38 +18,348 @@ use crate::context::ClientContext;
 pub fn SendLn() -> impl IntoView {
     let ClientContext { client, .. } = expect_context::<ClientContext>();
 
+    let (gateway_id, set_gateway_id) = create_signal(None);
+    let (payment_state, set_payment_state) = create_signal(None::<LnPaymentState>);
+    // Updates of earlier payments must not overwrite the state of the one
+    // shown
+    let active_operation = store_value(None::<OperationId>);
+    let (preview, set_preview) = create_signal(None::<InvoicePreview>);
+    let (pay_request, set_pay_request) = create_signal(None::<PayRequest>);
+    let (success_action, set_success_action) = create_signal(None::<SuccessAction>);
+    let (auth_request, set_auth_request) = create_signal(None::<AuthRequest>);
+
     let client = client.clone();
+    // Nothing is paid before the user confirmed the decoded invoice
+    let preview_action = create_action(move |invoice: &String| {
+        let invoice = invoice.trim().to_owned();
+        let gateway_id = gateway_id.get_untracked();
+        set_preview.set(None);
+        async move {
+            let preview = client
+                .get_value()
+                .preview_invoice(invoice, gateway_id)
+                .await?;
+            set_preview.set(Some(preview));
+            Ok::<_, RpcError>(())
+        }
+    });
+
+    // Lightning Addresses and LNURL-pay codes are resolved to an invoice first,
+    // which then goes through the same review step
+    let lnurl_action = create_action(move |url: &lnurl::Url| {
+        let url = url.clone();
+        set_preview.set(None);
+        set_pay_request.set(None);
+        set_success_action.set(None);
+        async move {
+            let pay_request = PayRequest::fetch(url).await.map_err(|e| format!("{e:#}"))?;
+            set_pay_request.set(Some(pay_request));
+            Ok::<_, String>(())
+        }
+    });
+
+    // LNURL-pay targets accept any amount in a range, so offer to pay as much
+    // as the balance allows after the gateway fee. They are the only targets
+    // with a Max button: invoices without an amount can't be paid yet.
+    // Refreshed for every target since the balance might have changed.
+    let max_payable_resource = create_resource(
+        move || (gateway_id.get(), lnurl_action.version().get()),
+        move |(gateway_id, _)| async move { client.get_value().max_payable(gateway_id).await },
+    );
+    let max_payable_msat = Signal::derive(move || max_payable_resource.get().and_then(Result::ok));
+
+    let lnurl_invoice_action =
+        create_action(move |(amount_msat, comment): &(u64, Option<String>)| {
+            let amount_msat = *amount_msat;
+            let comment = comment.clone();
+            let pay_request = pay_request.get_untracked();
+            async move {
+                let pay_request = pay_request.ok_or_else(|| "No LNURL-pay request".to_owned())?;
+                let response = pay_request
+                    .request_invoice(amount_msat, comment)
+                    .await
+                    .map_err(|e| format!("{e:#}"))?;
+                set_pay_request.set(None);
+                set_success_action.set(response.success_action);
+                preview_action.dispatch(response.invoice);
+                Ok::<_, String>(())
+            }
+        });
+
+    // LNURL-auth requests are scanned like payment requests, so they are
+    // handled here as well
+    let on_submit = move |input: String| {
+        set_auth_request.set(None);
+        match lnurl::parse(&input) {
+            Some(url) => match AuthRequest::from_url(&url) {
+                Some(request) => {
+                    set_preview.set(None);
+                    set_pay_request.set(None);
+                    set_auth_request.set(Some(request));
+                }
+                None => lnurl_action.dispatch(url),
+            },
+            None => {
+                set_pay_request.set(None);
+                set_success_action.set(None);
+                preview_action.dispatch(input)
+            }
+        }
+    };
+
     let submit_action = create_action(move |invoice: &String| {
         let invoice = invoice.clone();
-        async move { client.get_value().ln_send(invoice).await }
+        let gateway_id = gateway_id.get_untracked();
+        set_payment_state.set(None);
+        set_preview.set(None);
+        active_operation.set_value(None);
+        async move {
+            let (operation_id, mut updates) =
+                client.get_value().ln_send(invoice, gateway_id).await?;
+            active_operation.set_value(Some(operation_id));
+            spawn_local(async move {
+                while let Some(state) = updates.next().await {
+                    if active_operation.try_get_value() != Some(Some(operation_id)) {
+                        break;
+                    }
+                    let is_final = state.is_final();
+                    set_payment_state.set(Some(state));
+                    if is_final {
+                        break;
+                    }
+                }
+            });
+            Ok::<_, RpcError>(operation_id)
+        }
     });
 
     view! {
 
+      <GatewaySelector
+        on_change=move |gateway_id| set_gateway_id.set(gateway_id)
+        class="mb-8"
+      />
+
       <SubmitForm
-        description="Enter LN invoice (i.e. lnbcrt1p0…) to send a payment".into()
-        on_submit=move |v| submit_action.dispatch(v)
-        placeholder="LN invoice".into()
-        submit_label="Send".into()
-        loading=submit_action.pending()
+        description="Enter LN invoice (i.e. lnbcrt1p0…), LNURL or Lightning Address to send a payment".into()
+        on_submit=on_submit
+        placeholder="LN invoice, LNURL or user@domain".into()
+        submit_label="Review".into()
+        loading=Signal::derive(move || {
+          preview_action.pending().get()
+            || lnurl_action.pending().get()
+            || submit_action.pending().get()
+        })
         default_scan=true
       />
 
+      {move ||
+        lnurl_action.value().get().and_then(|result| result.err()).map(|error| view!(
+          <div class="text-body mt-4">
+            <span class="text-red-500">{format!("✗ Failed to resolve LNURL: {error}")}</span>
+          </div>
+        ))
+      }
 
       {move ||
-        if let Some(result) = submit_action.value().get() {
-          view!(
-            <div class="text-body mt-4">{
-              match result {
-                Err(error) => view!(<span class="text-red-500">{format!("✗ Failed to send invoice {error}")}</span>),
-                Ok(_) => view!(<span class="text-green-600">"✓ Invoice successfully sent"</span>)
-              }
-            }
-            </div>)
-        } else  {
-          view!(<div></div>)
+        auth_request.get().map(|auth_request| view!(
+          <LnurlAuth auth_request=auth_request on_cancel=move || set_auth_request.set(None) />
+        ))
+      }
+
+      {move ||
+        pay_request.get().map(|pay_request| view!(
+          <LnurlPayForm
+            pay_request=pay_request
+            max_payable_msat=max_payable_msat
+            on_submit=move |amount_msat, comment| lnurl_invoice_action.dispatch((amount_msat, comment))
+            loading=lnurl_invoice_action.pending()
+          />
+        ))
+      }
+
+      {move ||
+        lnurl_invoice_action.value().get().and_then(|result| result.err()).map(|error| view!(
+          <div class="text-body mt-4">
+            <span class="text-red-500">{format!("✗ Failed to get an invoice: {error}")}</span>
+          </div>
+        ))
+      }
+
+      {move ||
+        preview_action.value().get().and_then(|result| result.err()).map(|error| view!(
+          <div class="text-body mt-4">
+            <span class="text-red-500">{format!("✗ Can't pay this invoice: {error}")}</span>
+          </div>
+        ))
+      }
+
+      {move ||
+        preview.get().map(|preview| view!(
+          <InvoicePreviewCard
+            preview=preview
+            on_confirm=move |invoice| submit_action.dispatch(invoice)
+            on_cancel=move || set_preview.set(None)
+          />
+        ))
+      }
+
+
+      {move ||
+        match submit_action.value().get() {
+          Some(Err(error)) => view!(
+            <div class="text-body mt-4">
+              <span class="text-red-500">{format!("✗ Failed to send invoice {error}")}</span>
+            </div>
+          ).into_view(),
+          Some(Ok(_)) => view!(
+            <PaymentProgress state=payment_state success_action=success_action />
+          ).into_view(),
+          None => view!(<div></div>).into_view(),
         }
       }
 
     }
 }
+
+#[component]
+fn InvoicePreviewCard<C, X>(preview: InvoicePreview, on_confirm: C, on_cancel: X) -> impl IntoView
+where
+    C: Fn(String) + 'static + Copy,
+    X: Fn() + 'static + Copy,
+{
+    let clock = create_clock();
+    let expires_at = preview.expires_at;
+    let remaining_time = move || expires_at.duration_since(clock.get()).ok();
+    let remaining_balance_msat = preview.remaining_balance_msat();
+    let insufficient_balance = remaining_balance_msat < 0;
+    let missing_amount = preview.amount_msat.is_none();
+    let invoice = preview.invoice.clone();
+
+    let row = |label: &'static str, value: String| {
+        view! {
+            <div class="flex justify-between gap-4 py-1 border-b border-gray-200">
+                <span class="text-gray-400">{label}</span>
+                <span class="text-right break-all">{value}</span>
+            </div>
+        }
+    };
+
+    view! {
+        <div class="mt-8 font-body text-gray-600">
+            <h3 class="text-lg font-semibold mb-2">"Review payment"</h3>
+            {row(
+                "Amount",
+                preview
+                    .amount_msat
+                    .map_or("not specified".to_owned(), |amount| format!("{amount} msat")),
+            )}
+            {preview.description.clone().map(|description| row("Description", description))}
+            {preview.description_hash.clone().map(|hash| row("Description hash", hash))}
+            {row("Payee node", preview.payee.to_string())}
+            {row(
+                "Gateway",
+                preview.gateway_alias.clone().unwrap_or_else(|| "none (internal payments only)".to_owned()),
+            )}
+            {row("Estimated fee", format!("{} msat", preview.estimated_fee_msat))}
+            {row("Balance after payment", format!("{remaining_balance_msat} msat"))}
+            <div class="flex justify-between gap-4 py-1 border-b border-gray-200">
+                <span class="text-gray-400">"Expires in"</span>
+                <span>{move || remaining_time().map_or("expired".to_owned(), format_duration)}</span>
+            </div>
+
+            <Show when=move || remaining_time().is_none()>
+                <ErrorBlock class="mt-4">"The invoice has expired, ask the payee for a new one."</ErrorBlock>
+            </Show>
+            <Show when=move || insufficient_balance>
+                <WarningBlock class="mt-4">"Your balance is too low to pay this invoice."</WarningBlock>
+            </Show>
+            <Show when=move || missing_amount>
+                <WarningBlock class="mt-4">"Invoices without an amount can't be paid yet."</WarningBlock>
+            </Show>
+
+            <div class="flex space-x-4 mt-4">
+                <SubmitButton
+                    class="w-5/6"
+                    loading=Signal::derive(|| false)
+                    disabled=Signal::derive(move || {
+                        remaining_time().is_none() || insufficient_balance || missing_amount
+                    })
+                    on_click=move |_| on_confirm(invoice.clone())
+                >
+                    "Confirm and pay"
+                </SubmitButton>
+                <SubmitButton
+                    class="w-1/6"
+                    loading=Signal::derive(|| false)
+                    disabled=Signal::derive(|| false)
+                    on_click=move |_| on_cancel()
+                >
+                    "Cancel"
+                </SubmitButton>
+            </div>
+        </div>
+    }
+}
+
+#[component]
+fn PaymentProgress(
+    state: ReadSignal<Option<LnPaymentState>>,
+    success_action: ReadSignal<Option<SuccessAction>>,
+) -> impl IntoView {
+    move || {
+        let progress = |message: &'static str| {
+            view! {
+                <div class="flex items-center gap-2 text-body mt-4 text-gray-600">
+                    <LoaderIcon />
+                    <span>{message}</span>
+                </div>
+            }
+            .into_view()
+        };
+
+        match state.get() {
+            None | Some(LnPaymentState::Created) => progress("Funding the payment…"),
+            Some(LnPaymentState::Funded) => progress("Waiting for the payment to complete…"),
+            Some(LnPaymentState::WaitingForRefund) => progress(
+                "The gateway failed to pay the invoice, waiting for the refund of the locked funds…",
+            ),
+            Some(LnPaymentState::Success { preimage }) => {
+                let success_action = success_action.get_untracked().map(|action| {
+                    let message = action.message(&preimage).unwrap_or_else(|e| {
+                        format!("Failed to read the message of the recipient: {e}")
+                    });
+                    let url = action.url().map(|url| {
+                        view! {
+                            <a href=url.to_owned() target="_blank" rel="noopener noreferrer" class="block underline break-all">
+                                {url.to_owned()}
+                            </a>
+                        }
+                    });
+                    view! {
+                        <span class="block mt-2">{message}</span>
+                        {url}
+                    }
+                });
+                view! {
+                    <SuccessBlock class="mt-4">
+                        "The invoice has been paid!"
+                        {success_action}
+                        <span class="block text-xs break-all mt-2">{format!("Preimage: {preimage}")}</span>
+                    </SuccessBlock>
+                }
+                .into_view()
+            }
+            Some(LnPaymentState::Refunded { reason }) => view! {
+                <ErrorBlock class="mt-4">
+                    {format!("The payment failed and the funds were refunded to your wallet: {reason}")}
+                </ErrorBlock>
+            }
+            .into_view(),
+            Some(LnPaymentState::Failed { reason }) => view! {
+                <ErrorBlock class="mt-4">{format!("The payment failed: {reason}")}</ErrorBlock>
+            }
+            .into_view(),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use leptos::*;
+
+use super::{ErrorBlock, LoaderIcon};
+use crate::components::send_ecash::RECLAIM_TIMEOUT_OPTIONS;
+use crate::context::ClientContext;
+
+/// Choices for the number of notes above which they are consolidated
+/// automatically
+const CONSOLIDATION_THRESHOLD_OPTIONS: [(Option<u64>, &str); 5] = [
+    (None, "Never"),
+    (Some(50), "More than 50 notes"),
+    (Some(100), "More than 100 notes"),
+    (Some(200), "More than 200 notes"),
+    (Some(500), "More than 500 notes"),
+];
+
+//
+// Settings component
+// Per-wallet preferences
+//
+#[component]
+pub fn Settings() -> impl IntoView {
+    let ClientContext { client, .. } = expect_context::<ClientContext>();
+
+    let reclaim_timeout_resource = create_resource(
+        || (),
+        move |_| async move { client.get_value().get_ecash_reclaim_timeout().await },
+    );
+
+    let consolidation_threshold_resource = create_resource(
+        || (),
+        move |_| async move { client.get_value().get_note_consolidation_threshold().await },
+    );
+
+    let save_threshold_action = create_action(move |threshold: &Option<u64>| {
+        let threshold = *threshold;
+        async move {
+            client
+                .get_value()
+                .set_note_consolidation_threshold(threshold)
+                .await
+        }
+    });
+
+    let save_action = create_action(move |timeout: &Duration| {
+        let timeout = *timeout;
+        async move { client.get_value().set_ecash_reclaim_timeout(timeout).await }
+    });
+
+    view! {
+        <h3 class="text-lg font-semibold font-body text-gray-600">"E-cash"</h3>
+        <p class="mt-2 font-body text-gray-600">
+            "Notes you send are taken back into your wallet if the recipient hasn't redeemed them after:"
+        </p>
+        <Suspense fallback=move || view! { <LoaderIcon /> }>
+            {move || {
+                reclaim_timeout_resource.get().map(|result| match result {
+                    Ok(current) => view! {
+                        <select
+                            class="my-4 w-full font-body text-gray-600 border-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                            prop:disabled=move || save_action.pending().get()
+                            on:change=move |ev| {
+                                if let Some((timeout, _)) = event_target_value(&ev)
+                                    .parse::<usize>()
+                                    .ok()
+                                    .and_then(|idx| RECLAIM_TIMEOUT_OPTIONS.get(idx))
+                                {
+                                    save_action.dispatch(*timeout);
+                                }
+                            }
+                        >
+                            {RECLAIM_TIMEOUT_OPTIONS.iter().enumerate().map(|(idx, (timeout, name))| {
+                                view! {
+                                    <option value=idx.to_string() selected=*timeout == current>
+                                        {*name}
+                                    </option>
+                                }
+                            }).collect_view()}
+                        </select>
+                    }
+                    .into_view(),
+                    Err(e) => view! {
+                        <ErrorBlock class="my-4">
+                            {format!("Failed to load the reclaim timeout: {e}")}
+                        </ErrorBlock>
+                    }
+                    .into_view(),
+                })
+            }}
+        </Suspense>
+        {move || {
+            save_action.value().get().and_then(|result| result.err()).map(|e| {
+                view! {
+                    <ErrorBlock class="my-4">
+                        {format!("Failed to save the reclaim timeout: {e}")}
+                    </ErrorBlock>
+                }
+            })
+        }}
+        <p class="mt-2 font-body text-gray-600">
+            "Merge small notes into fewer larger ones automatically when the wallet holds:"
+        </p>
+        <p class="mt-1 text-sm text-gray-400">
+            "The federation's mint picks the denominations of the new notes. Notes aren't consolidated while payments are pending."
+        </p>
+        <Suspense fallback=move || view! { <LoaderIcon /> }>
+            {move || {
+                consolidation_threshold_resource.get().map(|result| match result {
+                    Ok(current) => view! {
+                        <select
+                            class="my-4 w-full font-body text-gray-600 border-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                            prop:disabled=move || save_threshold_action.pending().get()
+                            on:change=move |ev| {
+                                if let Some((threshold, _)) = event_target_value(&ev)
+                                    .parse::<usize>()
+                                    .ok()
+                                    .and_then(|idx| CONSOLIDATION_THRESHOLD_OPTIONS.get(idx))
+                                {
+                                    save_threshold_action.dispatch(*threshold);
+                                }
+                            }
+                        >
+                            {CONSOLIDATION_THRESHOLD_OPTIONS.iter().enumerate().map(|(idx, (threshold, name))| {
+                                view! {
+                                    <option value=idx.to_string() selected=*threshold == current>
+                                        {*name}
+                                    </option>
+                                }
+                            }).collect_view()}
+                        </select>
+                    }
+                    .into_view(),
+                    Err(e) => view! {
+                        <ErrorBlock class="my-4">
+                            {format!("Failed to load the consolidation setting: {e}")}
+                        </ErrorBlock>
+                    }
+                    .into_view(),
+                })
+            }}
+        </Suspense>
+        {move || {
+            save_threshold_action.value().get().and_then(|result| result.err()).map(|e| {
+                view! {
+                    <ErrorBlock class="my-4">
+                        {format!("Failed to save the consolidation setting: {e}")}
+                    </ErrorBlock>
+                }
+            })
+        }}
+    }
+}
@@ -6,7 +6,7 @@ use crate::utils::empty_view;
 
 #[component]
 pub fn SubmitButton<F>(
-    loading: ReadSignal<bool>,
+    #[prop(into)] loading: Signal<bool>,
     disabled: Signal<bool>,
     on_click: F,
     #[prop(optional, into)] class: String,
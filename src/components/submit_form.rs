@@ -12,7 +12,7 @@ pub fn SubmitForm<F>(
     placeholder: String,
     description: String,
     submit_label: String,
-    loading: ReadSignal<bool>,
+    #[prop(into)] loading: Signal<bool>,
     #[prop(default = false)] default_scan: bool,
 ) -> impl IntoView
 where
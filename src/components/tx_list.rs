@@ -1,37 +1,258 @@
+use fedimint_core::core::OperationId;
+use leptos::logging::warn;
 use leptos::*;
 
-use crate::client::Transaction;
-use crate::components::LoaderIcon;
+use crate::client::{
+    Transaction, TransactionDirection, TransactionFilter, TransactionQuery, TransactionSort,
+    TransactionStatus,
+};
+use crate::components::{ErrorBlock, LoaderIcon};
 use crate::context::ClientContext;
+use crate::db::OperationLabel;
+use crate::utils::{
+    create_clock, download_file, format_duration, format_timestamp, parse_date, parse_date_end,
+    ExportFormat,
+};
 
 //
 // Receive e-cash component
 //
 #[component]
-pub fn TxList<F>(update_signal: F) -> impl IntoView
-where
-    F: Fn() + Copy + 'static,
-{
+pub fn TxList() -> impl IntoView {
+    const PAGE_SIZE: usize = 20;
+
     let ClientContext { client, .. } = expect_context::<ClientContext>();
 
-    let tx_list_resource = create_resource(
+    let (operation_kind, set_operation_kind) = create_signal(None::<String>);
+    let (direction, set_direction) = create_signal(None::<TransactionDirection>);
+    let (status, set_status) = create_signal(None::<TransactionStatus>);
+    let (from, set_from) = create_signal(String::new());
+    let (until, set_until) = create_signal(String::new());
+    let (search, set_search) = create_signal(String::new());
+    let (sort, set_sort) = create_signal(TransactionSort::default());
+    let (page, set_page) = create_signal(0usize);
+
+    let query_filter = create_memo(move |_| TransactionFilter {
+        operation_kind: operation_kind.get(),
+        direction: direction.get(),
+        status: status.get(),
+        from: parse_date(&from.get()),
+        until: parse_date_end(&until.get()),
+        search: Some(search.get()).filter(|search| !search.is_empty()),
+    });
+    let query = move || TransactionQuery {
+        filter: query_filter.get(),
+        sort: sort.get(),
+        offset: page.get() * PAGE_SIZE,
+        limit: PAGE_SIZE,
+    };
+
+    let tx_list_resource = create_resource(query, move |query| async move {
+        let client = client.get_value();
+        client
+            .list_transactions(query)
+            .await
+            .expect("list tx failed")
+    });
+
+    let transaction_updates = create_local_resource(
         || (),
         move |()| async move {
-            let client = client.get_value();
-            client.list_transactions().await.expect("list tx failed")
+            let updates = match client.get_value().subscribe_transactions().await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    warn!("client could not subscribe to transactions: {e:?}");
+                    std::future::pending().await
+                }
+            };
+            create_signal_from_stream(updates)
         },
     );
 
-    create_effect(move |p| {
-        update_signal();
+    // Update transactions on the current page in place, anything else might
+    // change which transactions belong on the page, so it's fetched again
+    create_effect(move |_| {
+        let Some(transaction) = transaction_updates.get().and_then(|updates| updates.get()) else {
+            return;
+        };
 
-        if matches!(p, Some(_)) {
-            tx_list_resource.refetch()
+        let mut updated_in_place = false;
+        tx_list_resource.update(|page| {
+            let Some(page) = page else {
+                return;
+            };
+            let Some(listed) = page
+                .transactions
+                .iter_mut()
+                .find(|tx| tx.operation_id == transaction.operation_id)
+            else {
+                return;
+            };
+            if query_filter.with_untracked(|filter| filter.matches(&transaction)) {
+                *listed = transaction;
+                updated_in_place = true;
+            }
+        });
+
+        if !updated_in_place {
+            tx_list_resource.refetch();
         }
     });
 
+    // Exports ignore everything but the date range and always contain all
+    // matching transactions, not just the current page
+    let export_action = create_action(move |format: &ExportFormat| {
+        let format = *format;
+        let filter = TransactionFilter {
+            from: parse_date(&from.get_untracked()),
+            until: parse_date_end(&until.get_untracked()),
+            ..Default::default()
+        };
+        async move {
+            let page = client
+                .get_value()
+                .list_transactions(TransactionQuery {
+                    filter,
+                    sort: TransactionSort::Oldest,
+                    offset: 0,
+                    limit: usize::MAX,
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+            download_file(
+                format.file_name(),
+                format.mime_type(),
+                &format.export(&page.transactions),
+            )
+            .map_err(|e| format!("{e:?}"))
+        }
+    });
+
+    let total = move || {
+        tx_list_resource
+            .get()
+            .map(|page| page.total)
+            .unwrap_or_default()
+    };
+    // Keeps rows that are rebuilt for other reasons from showing an outdated
+    // label
+    let update_label = move |operation_id: OperationId, label: OperationLabel| {
+        tx_list_resource.update(|page| {
+            if let Some(transaction) = page.as_mut().and_then(|page| {
+                page.transactions
+                    .iter_mut()
+                    .find(|tx| tx.operation_id == operation_id)
+            }) {
+                transaction.set_label(label);
+            }
+        });
+    };
+
+    let has_previous_page = move || page.get() > 0;
+    let has_next_page = move || (page.get() + 1) * PAGE_SIZE < total();
+
+    const SELECT_CLASS: &str =
+        "font-body text-gray-600 border-gray-400 ring-0 focus:border-blue-400 focus:ring-0";
+
     view! {
         <div>
+            <div class="grid grid-cols-2 md:grid-cols-4 gap-2 mb-4 text-sm">
+                <select
+                    class=SELECT_CLASS
+                    on:change=move |ev| {
+                        let kind = event_target_value(&ev);
+                        set_operation_kind.set(Some(kind).filter(|kind| !kind.is_empty()));
+                        set_page.set(0);
+                    }
+                >
+                    <option value="">"All types"</option>
+                    <option value="ln">"Lightning"</option>
+                    <option value="mint">"E-Cash"</option>
+                </select>
+                <select
+                    class=SELECT_CLASS
+                    on:change=move |ev| {
+                        set_direction.set(match event_target_value(&ev).as_str() {
+                            "in" => Some(TransactionDirection::Incoming),
+                            "out" => Some(TransactionDirection::Outgoing),
+                            _ => None,
+                        });
+                        set_page.set(0);
+                    }
+                >
+                    <option value="">"In & out"</option>
+                    <option value="in">"Incoming"</option>
+                    <option value="out">"Outgoing"</option>
+                </select>
+                <select
+                    class=SELECT_CLASS
+                    on:change=move |ev| {
+                        set_status.set(match event_target_value(&ev).as_str() {
+                            "pending" => Some(TransactionStatus::Pending),
+                            "success" => Some(TransactionStatus::Success),
+                            "failed" => Some(TransactionStatus::Failed),
+                            "refunded" => Some(TransactionStatus::Refunded),
+                            "canceled" => Some(TransactionStatus::Canceled),
+                            "expired" => Some(TransactionStatus::Expired),
+                            _ => None,
+                        });
+                        set_page.set(0);
+                    }
+                >
+                    <option value="">"Any status"</option>
+                    <option value="pending">"Pending"</option>
+                    <option value="success">"Successful"</option>
+                    <option value="failed">"Failed"</option>
+                    <option value="refunded">"Refunded"</option>
+                    <option value="canceled">"Canceled"</option>
+                    <option value="expired">"Expired"</option>
+                </select>
+                <select
+                    class=SELECT_CLASS
+                    on:change=move |ev| {
+                        set_sort.set(match event_target_value(&ev).as_str() {
+                            "oldest" => TransactionSort::Oldest,
+                            "largest" => TransactionSort::Largest,
+                            "smallest" => TransactionSort::Smallest,
+                            _ => TransactionSort::Newest,
+                        });
+                        set_page.set(0);
+                    }
+                >
+                    <option value="newest">"Newest first"</option>
+                    <option value="oldest">"Oldest first"</option>
+                    <option value="largest">"Largest first"</option>
+                    <option value="smallest">"Smallest first"</option>
+                </select>
+                <input
+                    type="date"
+                    class=SELECT_CLASS
+                    on:change=move |ev| {
+                        set_from.set(event_target_value(&ev));
+                        set_page.set(0);
+                    }
+                    prop:value=move || from.get()
+                />
+                <input
+                    type="date"
+                    class=SELECT_CLASS
+                    on:change=move |ev| {
+                        set_until.set(event_target_value(&ev));
+                        set_page.set(0);
+                    }
+                    prop:value=move || until.get()
+                />
+                <input
+                    type="search"
+                    placeholder="Search descriptions, labels and tags"
+                    class=format!("col-span-2 {SELECT_CLASS} placeholder:text-gray-400")
+                    on:input=move |ev| {
+                        set_search.set(event_target_value(&ev));
+                        set_page.set(0);
+                    }
+                    prop:value=move || search.get()
+                />
+            </div>
             <Suspense
                 fallback=move || {view! {<LoaderIcon />}}
             >
@@ -44,24 +265,158 @@ where
                         </tr>
                     </thead>
                     <tbody>
-                    {move || {
-                        tx_list_resource.get().map(|transactions| {
-                            transactions.into_iter()
-                                .map(|tx| {
-                                    view! {<TxListRow transaction=tx />}
-                                })
-                                .collect::<Vec<_>>()
-                        })
-                    }}
+                    // Rows are only rebuilt if their status changed, so updates of
+                    // other transactions don't discard a label being edited
+                    <For
+                        each=move || {
+                            tx_list_resource
+                                .get()
+                                .map(|page| page.transactions)
+                                .unwrap_or_default()
+                        }
+                        key=|tx| (tx.operation_id, tx.status)
+                        children=move |tx| {
+                            let operation_id = tx.operation_id;
+                            view! {
+                                <TxListRow
+                                    transaction=tx
+                                    on_label_saved=move |label| update_label(operation_id, label)
+                                />
+                            }
+                        }
+                    />
                     </tbody>
                 </table>
+                <div class="flex justify-between items-center mt-4 text-sm text-gray-600">
+                    <button
+                        class="px-4 py-2 text-blue-500 disabled:text-gray-300"
+                        disabled=move || !has_previous_page()
+                        on:click=move |_| set_page.update(|page| *page = page.saturating_sub(1))
+                    >
+                        "Previous"
+                    </button>
+                    <span>
+                        {move || {
+                            let total = total();
+                            let first = (page.get() * PAGE_SIZE).min(total);
+                            let last = (first + PAGE_SIZE).min(total);
+                            format!("{}–{last} of {total}", if total == 0 { 0 } else { first + 1 })
+                        }}
+                    </span>
+                    <button
+                        class="px-4 py-2 text-blue-500 disabled:text-gray-300"
+                        disabled=move || !has_next_page()
+                        on:click=move |_| set_page.update(|page| *page += 1)
+                    >
+                        "Next"
+                    </button>
+                </div>
             </Suspense>
+            <div class="flex items-center gap-4 mt-8 text-sm text-gray-600">
+                <span class="flex-grow">"Export the full history, limited to the selected dates if any:"</span>
+                <button
+                    class="px-4 py-2 text-blue-500 disabled:text-gray-300"
+                    disabled=move || export_action.pending().get()
+                    on:click=move |_| export_action.dispatch(ExportFormat::Csv)
+                >
+                    "CSV"
+                </button>
+                <button
+                    class="px-4 py-2 text-blue-500 disabled:text-gray-300"
+                    disabled=move || export_action.pending().get()
+                    on:click=move |_| export_action.dispatch(ExportFormat::Json)
+                >
+                    "JSON"
+                </button>
+            </div>
+            {move || {
+                export_action.value().get().and_then(|result| result.err()).map(|e| {
+                    view! {
+                        <ErrorBlock class="mt-4">
+                            {format!("Export failed: {e}")}
+                        </ErrorBlock>
+                    }
+                })
+            }}
         </div>
     }
 }
 
 #[component]
-pub fn TxListRow(transaction: Transaction) -> impl IntoView {
+pub fn TxListRow<F>(transaction: Transaction, on_label_saved: F) -> impl IntoView
+where
+    F: Fn(OperationLabel) + 'static + Copy,
+{
+    let ClientContext { client, .. } = expect_context::<ClientContext>();
+
+    let operation_id = transaction.operation_id;
+    let saved_label = create_rw_signal(OperationLabel {
+        label: transaction.label.clone().unwrap_or_default(),
+        tags: transaction.tags.clone(),
+    });
+    let (editing, set_editing) = create_signal(false);
+    let (label_input, set_label_input) = create_signal(String::new());
+    let (tags_input, set_tags_input) = create_signal(String::new());
+
+    let save_action = create_action(move |label: &OperationLabel| {
+        let label = label.clone();
+        async move {
+            client
+                .get_value()
+                .set_operation_label(operation_id, label.clone())
+                .await
+                .map(|()| {
+                    let label = label.normalized();
+                    saved_label.set(label.clone());
+                    on_label_saved(label);
+                })
+        }
+    });
+
+    let start_editing = move || {
+        saved_label.with_untracked(|saved| {
+            set_label_input.set(saved.label.clone());
+            set_tags_input.set(saved.tags.join(", "));
+        });
+        set_editing.set(true);
+    };
+    let save = move || {
+        save_action.dispatch(OperationLabel {
+            label: label_input.get_untracked(),
+            tags: tags_input
+                .get_untracked()
+                .split(',')
+                .map(str::to_owned)
+                .collect(),
+        });
+        set_editing.set(false);
+    };
+
+    // Out-of-band ecash is only final once the recipient redeemed it or we
+    // took it back, so tell the user which one happened
+    let ecash_spend = transaction.operation_kind == "mint"
+        && transaction.direction == TransactionDirection::Outgoing;
+    let status = match transaction.status {
+        TransactionStatus::Pending if ecash_spend => Some("not redeemed yet"),
+        TransactionStatus::Success if ecash_spend => Some("redeemed by recipient"),
+        TransactionStatus::Refunded if ecash_spend => Some("reclaimed"),
+        TransactionStatus::Pending => Some("pending"),
+        TransactionStatus::Success => None,
+        TransactionStatus::Failed => Some("failed"),
+        TransactionStatus::Refunded => Some("refunded"),
+        TransactionStatus::Canceled => Some("canceled"),
+        TransactionStatus::Expired => Some("expired"),
+    };
+    let reclaimable = ecash_spend && transaction.status == TransactionStatus::Pending;
+
+    let reclaim_deadline = transaction.reclaim_deadline.filter(|_| reclaimable);
+    let clock = create_clock();
+
+    let reclaim_action =
+        create_action(
+            move |_: &()| async move { client.get_value().reclaim_ecash(operation_id).await },
+        );
+
     view! {
         <tr class="border-y border-slate-300">
             <td class="text-center p-4">
@@ -80,7 +435,95 @@ pub fn TxListRow(transaction: Transaction) -> impl IntoView {
                 }
             </td>
             <td class="p-4">
-                {transaction.description}
+                <Show
+                    when=move || editing.get()
+                    fallback=move || view! {
+                        <p class="font-semibold">{move || saved_label.with(|saved| saved.label.clone())}</p>
+                    }
+                >
+                    <input
+                        type="text"
+                        placeholder="Label"
+                        class="w-full mb-2 text-sm font-body text-gray-600 border-gray-400 placeholder:text-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                        on:input=move |ev| set_label_input.set(event_target_value(&ev))
+                        prop:value=move || label_input.get()
+                    />
+                    <input
+                        type="text"
+                        placeholder="Tags, separated by commas"
+                        class="w-full mb-2 text-sm font-body text-gray-600 border-gray-400 placeholder:text-gray-400 ring-0 focus:border-blue-400 focus:ring-0"
+                        on:input=move |ev| set_tags_input.set(event_target_value(&ev))
+                        prop:value=move || tags_input.get()
+                    />
+                </Show>
+                <p>{transaction.description}</p>
+                <p class="flex flex-wrap gap-1">
+                    {move || saved_label.with(|saved| saved.tags.iter().map(|tag| {
+                        let tag = tag.clone();
+                        view! {
+                            <span class="px-2 rounded-full bg-slate-100 text-xs text-gray-600">{tag}</span>
+                        }
+                    }).collect_view())}
+                </p>
+                <p class="text-xs text-gray-400">
+                    {format_timestamp(transaction.timestamp)}
+                    {status.map(|status| format!(" · {status}"))}
+                    " · "
+                    <Show
+                        when=move || editing.get()
+                        fallback=move || view! {
+                            <button class="text-blue-500" on:click=move |_| start_editing()>
+                                "Edit label"
+                            </button>
+                        }
+                    >
+                        <button class="text-blue-500" on:click=move |_| save()>"Save"</button>
+                        " · "
+                        <button class="text-blue-500" on:click=move |_| set_editing.set(false)>
+                            "Cancel"
+                        </button>
+                    </Show>
+                </p>
+                {move || {
+                    save_action.value().get().and_then(|result| result.err()).map(|e| {
+                        view! {
+                            <p class="text-xs text-red-500">{format!("Failed to save label: {e}")}</p>
+                        }
+                    })
+                }}
+                {move || {
+                    reclaim_deadline
+                        .filter(|_| reclaim_action.value().get().is_none())
+                        .map(|deadline| {
+                            let message = match deadline.duration_since(clock.get()) {
+                                Ok(remaining) => format!(
+                                    "Reclaims automatically in {}",
+                                    format_duration(remaining)
+                                ),
+                                Err(_) => "Reclaiming automatically…".to_owned(),
+                            };
+                            view! { <p class="text-xs text-gray-600">{message}</p> }
+                        })
+                }}
+                <Show when=move || reclaimable && reclaim_action.value().get().is_none()>
+                    <button
+                        class="text-xs text-blue-500 disabled:text-gray-400"
+                        prop:disabled=move || reclaim_action.pending().get()
+                        on:click=move |_| reclaim_action.dispatch(())
+                    >
+                        {move || if reclaim_action.pending().get() { "Reclaiming…" } else { "Reclaim now" }}
+                    </button>
+                </Show>
+                {move || {
+                    reclaim_action.value().get().map(|result| {
+                        let message = match result {
+                            Ok(true) => "The notes were reclaimed into your wallet".to_owned(),
+                            Ok(false) => "Too late, the recipient already redeemed the notes".to_owned(),
+                            Err(e) => format!("Failed to reclaim the notes: {e}"),
+                        };
+                        view! { <p class="text-xs text-gray-600">{message}</p> }
+                    })
+                }}
             </td>
             <td class="p-4">
                 <p
@@ -1,17 +1,22 @@
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use anyhow::Result;
+use fedimint_core::core::OperationId;
 use fedimint_core::db::mem_impl::{MemDatabase, MemTransaction};
 use fedimint_core::db::{
     IDatabaseTransactionOps, IDatabaseTransactionOpsCore, IRawDatabase, IRawDatabaseTransaction,
     PrefixStream,
 };
+use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::module::__reexports::serde_json;
-use fedimint_core::{apply, async_trait_maybe_send};
+use fedimint_core::{apply, async_trait_maybe_send, impl_db_lookup, impl_db_record};
 use futures::StreamExt;
 use gloo_storage::Storage;
+use secp256k1_zkp::PublicKey;
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
 #[derive(Clone, Debug)]
@@ -43,6 +48,12 @@ impl PersistentMemDb {
         PersistentMemDb(Arc::new(db), name)
     }
 
+    /// Removes the stored data, the in-memory copy stays usable and is
+    /// written again on the next commit
+    pub fn delete(&self) {
+        gloo_storage::LocalStorage::delete(&self.1);
+    }
+
     pub fn list_dbs() -> Vec<String> {
         gloo_storage::LocalStorage::get_all::<BTreeMap<String, serde_json::Value>>()
             .unwrap()
@@ -129,3 +140,146 @@ impl<'a> IRawDatabaseTransaction for PersistentMemDbTransaction<'a> {
         Ok(())
     }
 }
+
+/// Key prefixes of the data Webimint stores in the client database itself.
+/// `fedimint-client` leaves the range starting at `0xb0` to applications
+/// embedding it.
+#[repr(u8)]
+#[derive(Clone, Debug)]
+pub enum DbKeyPrefix {
+    OperationLabel = 0xb0,
+    PreferredGateway = 0xb1,
+    GatewayLastSeen = 0xb2,
+    GatewayVettingPolicy = 0xb3,
+    EcashReclaimTimeout = 0xb4,
+    NoteConsolidationThreshold = 0xb5,
+}
+
+/// User-provided label and tags of an operation
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct OperationLabelKey(pub OperationId);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct OperationLabelKeyPrefix;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize)]
+pub struct OperationLabel {
+    pub label: String,
+    pub tags: Vec<String>,
+}
+
+impl OperationLabel {
+    pub fn is_empty(&self) -> bool {
+        self.label.is_empty() && self.tags.is_empty()
+    }
+
+    /// Trims the label and tags and drops empty or duplicate tags
+    pub fn normalized(self) -> OperationLabel {
+        let mut tags = Vec::<String>::new();
+        for tag in self.tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !tags.iter().any(|existing| existing == tag) {
+                tags.push(tag.to_owned());
+            }
+        }
+
+        OperationLabel {
+            label: self.label.trim().to_owned(),
+            tags,
+        }
+    }
+}
+
+impl_db_record!(
+    key = OperationLabelKey,
+    value = OperationLabel,
+    db_prefix = DbKeyPrefix::OperationLabel,
+);
+impl_db_lookup!(
+    key = OperationLabelKey,
+    query_prefix = OperationLabelKeyPrefix
+);
+
+/// Gateway the user pinned to be used for Lightning payments by default
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct PreferredGatewayKey;
+
+impl_db_record!(
+    key = PreferredGatewayKey,
+    value = PublicKey,
+    db_prefix = DbKeyPrefix::PreferredGateway,
+);
+
+/// Last time the federation listed the gateway as registered
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct GatewayLastSeenKey(pub PublicKey);
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct GatewayLastSeenKeyPrefix;
+
+impl_db_record!(
+    key = GatewayLastSeenKey,
+    value = SystemTime,
+    db_prefix = DbKeyPrefix::GatewayLastSeen,
+);
+impl_db_lookup!(
+    key = GatewayLastSeenKey,
+    query_prefix = GatewayLastSeenKeyPrefix
+);
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct GatewayVettingPolicyKey;
+
+/// Which of the gateways registered with the federation the wallet may use
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Encodable, Decodable, Serialize, Deserialize,
+)]
+pub enum GatewayVettingPolicy {
+    /// Only use gateways listed in the federation's `vetted_gateways` meta
+    /// field
+    #[default]
+    VettedOnly,
+    /// Use vetted gateways if there are any, any other gateway otherwise
+    VettedPreferred,
+    /// Treat all gateways the same
+    Any,
+}
+
+impl GatewayVettingPolicy {
+    pub fn allows(self, vetted: bool) -> bool {
+        vetted || self != GatewayVettingPolicy::VettedOnly
+    }
+
+    /// Whether vetted gateways should be picked over other allowed ones
+    pub fn prefers_vetted(self) -> bool {
+        self != GatewayVettingPolicy::Any
+    }
+}
+
+impl_db_record!(
+    key = GatewayVettingPolicyKey,
+    value = GatewayVettingPolicy,
+    db_prefix = DbKeyPrefix::GatewayVettingPolicy,
+);
+
+/// Default time in seconds after which ecash spends that weren't redeemed are
+/// reclaimed automatically
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct EcashReclaimTimeoutKey;
+
+impl_db_record!(
+    key = EcashReclaimTimeoutKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::EcashReclaimTimeout,
+);
+
+/// Number of notes above which the wallet consolidates its notes
+/// automatically, automatic consolidation is off without an entry
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct NoteConsolidationThresholdKey;
+
+impl_db_record!(
+    key = NoteConsolidationThresholdKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::NoteConsolidationThreshold,
+);
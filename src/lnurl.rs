@@ -0,0 +1,805 @@
+//! Client side of the LNURL protocols, see <https://github.com/lnurl/luds>
+
+use std::str::FromStr;
+
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, KeyIvInit};
+use anyhow::Context;
+use base64::Engine;
+use bitcoin::hashes::{sha256, Hash};
+use fedimint_core::module::__reexports::serde_json;
+use lightning_invoice::{Bolt11Invoice, Bolt11InvoiceDescription};
+pub use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Extracts the URL behind a bech32 encoded `lnurl1…` string, a LUD-17
+/// `lnurlp://`-style URL or a LUD-16 Lightning Address (`user@domain`).
+/// Returns `None` if `input` is neither, e.g. because it is an invoice.
+pub fn parse(input: &str) -> Option<Url> {
+    let input = input.trim();
+    let input = strip_prefix_ignore_case(input, "lightning:").unwrap_or(input);
+
+    if input.len() > 6 && input[..6].eq_ignore_ascii_case("lnurl1") {
+        let (hrp, data, _) = bech32::decode(input).ok()?;
+        if hrp != "lnurl" {
+            return None;
+        }
+        let bytes = <Vec<u8> as bech32::FromBase32>::from_base32(&data).ok()?;
+        return Url::parse(&String::from_utf8(bytes).ok()?).ok();
+    }
+
+    for scheme in ["lnurlp://", "lnurlw://", "lnurlc://", "keyauth://"] {
+        if let Some(rest) = strip_prefix_ignore_case(input, scheme) {
+            let url = Url::parse(&format!("https://{rest}")).ok()?;
+            return Some(with_onion_scheme(url));
+        }
+    }
+
+    let (user, domain) = input.split_once('@')?;
+    let valid_user = !user.is_empty()
+        && user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c));
+    if !valid_user || domain.is_empty() || domain.contains(['/', '@']) {
+        return None;
+    }
+    let url = Url::parse(&format!(
+        "https://{domain}/.well-known/lnurlp/{}",
+        user.to_ascii_lowercase()
+    ))
+    .ok()?;
+    Some(with_onion_scheme(url))
+}
+
+fn strip_prefix_ignore_case<'a>(input: &'a str, prefix: &str) -> Option<&'a str> {
+    input
+        .get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &input[prefix.len()..])
+}
+
+/// Tor hidden services are reached over plain HTTP
+fn with_onion_scheme(mut url: Url) -> Url {
+    if url.host_str().is_some_and(|host| host.ends_with(".onion")) {
+        let _ = url.set_scheme("http");
+    }
+    url
+}
+
+/// Fetches `url` and deserializes the response, turning LNURL error
+/// responses (`{"status": "ERROR", "reason": …}`) into errors.
+async fn get_json<T: DeserializeOwned>(url: Url) -> anyhow::Result<T> {
+    let host = url.host_str().unwrap_or_default().to_owned();
+    let response = reqwest::get(url)
+        .await
+        .with_context(|| format!("Failed to reach {host}"))?
+        .json::<serde_json::Value>()
+        .await
+        .with_context(|| format!("Invalid response from {host}"))?;
+
+    if response.get("status").and_then(|s| s.as_str()) == Some("ERROR") {
+        let reason = response
+            .get("reason")
+            .and_then(|r| r.as_str())
+            .unwrap_or("unknown error");
+        anyhow::bail!("{host} returned an error: {reason}");
+    }
+
+    serde_json::from_value(response).with_context(|| format!("Invalid response from {host}"))
+}
+
+/// LUD-06 `payRequest`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayRequest {
+    pub callback: String,
+    pub min_sendable: u64,
+    pub max_sendable: u64,
+    pub metadata: String,
+    /// Maximum length of a comment sent along with the payment (LUD-12)
+    #[serde(default)]
+    pub comment_allowed: usize,
+    tag: String,
+    /// Host the request was fetched from
+    #[serde(skip)]
+    pub domain: String,
+}
+
+impl PayRequest {
+    pub async fn fetch(url: Url) -> anyhow::Result<PayRequest> {
+        let domain = url.host_str().unwrap_or_default().to_owned();
+        let mut pay_request = get_json::<PayRequest>(url).await?;
+        if pay_request.tag != "payRequest" {
+            anyhow::bail!("Expected a LNURL-pay request, got {}", pay_request.tag);
+        }
+        if pay_request.min_sendable > pay_request.max_sendable {
+            anyhow::bail!("{domain} returned an empty amount range");
+        }
+        pay_request.domain = domain;
+        Ok(pay_request)
+    }
+
+    /// The `text/plain` entry of the metadata, which describes the payment
+    pub fn description(&self) -> Option<String> {
+        serde_json::from_str::<Vec<(String, serde_json::Value)>>(&self.metadata)
+            .ok()?
+            .into_iter()
+            .find(|(mime_type, _)| mime_type == "text/plain")
+            .and_then(|(_, value)| value.as_str().map(ToOwned::to_owned))
+    }
+
+    /// Asks the service for an invoice over `amount_msat` and checks that it
+    /// commits to this pay request's metadata.
+    pub async fn request_invoice(
+        &self,
+        amount_msat: u64,
+        comment: Option<String>,
+    ) -> anyhow::Result<PayResponse> {
+        let comment = self.check_request(amount_msat, comment)?;
+
+        let mut callback = Url::parse(&self.callback).context("Invalid callback URL")?;
+        callback
+            .query_pairs_mut()
+            .append_pair("amount", &amount_msat.to_string());
+        if let Some(comment) = &comment {
+            callback.query_pairs_mut().append_pair("comment", comment);
+        }
+        let callback_domain = callback.host_str().unwrap_or_default().to_owned();
+
+        let response = get_json::<RawPayResponse>(callback).await?;
+        let invoice = Bolt11Invoice::from_str(&response.pr)
+            .map_err(|e| anyhow::anyhow!("The service returned an invalid invoice: {e:?}"))?;
+        if invoice.amount_milli_satoshis() != Some(amount_msat) {
+            anyhow::bail!("The invoice returned by the service is for a different amount");
+        }
+        self.check_description(invoice.description())?;
+
+        let success_action = response
+            .success_action
+            .filter(|action| action.is_issued_by(&callback_domain));
+
+        Ok(PayResponse {
+            invoice: response.pr,
+            success_action,
+        })
+    }
+
+    /// Checks that the amount is in the accepted range and the comment isn't
+    /// too long. Returns the comment to send, if any.
+    fn check_request(
+        &self,
+        amount_msat: u64,
+        comment: Option<String>,
+    ) -> anyhow::Result<Option<String>> {
+        if !(self.min_sendable..=self.max_sendable).contains(&amount_msat) {
+            anyhow::bail!(
+                "The amount has to be between {} and {} msat",
+                self.min_sendable,
+                self.max_sendable
+            );
+        }
+        let comment = comment.filter(|comment| !comment.is_empty());
+        if let Some(comment) = &comment {
+            if comment.chars().count() > self.comment_allowed {
+                anyhow::bail!(
+                    "The comment can be at most {} characters long",
+                    self.comment_allowed
+                );
+            }
+        }
+        Ok(comment)
+    }
+
+    /// Invoices have to commit to the hash of the metadata the user saw
+    fn check_description(&self, description: Bolt11InvoiceDescription) -> anyhow::Result<()> {
+        let metadata_hash = sha256::Hash::hash(self.metadata.as_bytes());
+        match description {
+            Bolt11InvoiceDescription::Hash(hash) if hash.0 == metadata_hash => Ok(()),
+            _ => anyhow::bail!("The invoice returned by the service doesn't match its metadata"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawPayResponse {
+    pr: String,
+    #[serde(default)]
+    success_action: Option<SuccessAction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayResponse {
+    pub invoice: String,
+    pub success_action: Option<SuccessAction>,
+}
+
+/// What to show the user once a LNURL payment succeeded (LUD-09, LUD-10)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "tag", rename_all = "lowercase")]
+pub enum SuccessAction {
+    Message {
+        message: String,
+    },
+    Url {
+        description: String,
+        url: String,
+    },
+    Aes {
+        description: String,
+        ciphertext: String,
+        iv: String,
+    },
+}
+
+impl SuccessAction {
+    /// Message to display, AES encrypted secrets are decrypted using the hex
+    /// encoded payment `preimage`.
+    pub fn message(&self, preimage: &str) -> anyhow::Result<String> {
+        match self {
+            SuccessAction::Message { message } => Ok(message.clone()),
+            SuccessAction::Url { description, .. } => Ok(description.clone()),
+            SuccessAction::Aes {
+                description,
+                ciphertext,
+                iv,
+            } => {
+                let key = hex::decode(preimage).context("Invalid preimage")?;
+                let ciphertext = base64::engine::general_purpose::STANDARD
+                    .decode(ciphertext)
+                    .context("Invalid ciphertext")?;
+                let iv = base64::engine::general_purpose::STANDARD
+                    .decode(iv)
+                    .context("Invalid IV")?;
+                let plaintext = cbc::Decryptor::<aes::Aes256>::new_from_slices(&key, &iv)
+                    .map_err(|_| anyhow::anyhow!("Invalid key or IV length"))?
+                    .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+                    .map_err(|_| anyhow::anyhow!("Failed to decrypt the secret"))?;
+                Ok(format!(
+                    "{description}: {}",
+                    String::from_utf8_lossy(&plaintext)
+                ))
+            }
+        }
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            SuccessAction::Url { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+
+    /// LUD-09: URLs have to point to the domain that issued the invoice
+    fn is_issued_by(&self, domain: &str) -> bool {
+        match self {
+            SuccessAction::Url { url, .. } => {
+                Url::parse(url).is_ok_and(|url| url.host_str() == Some(domain))
+            }
+            _ => true,
+        }
+    }
+}
+
+/// LUD-03 `withdrawRequest`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawRequest {
+    pub callback: String,
+    pub k1: String,
+    #[serde(default)]
+    pub default_description: String,
+    pub min_withdrawable: u64,
+    pub max_withdrawable: u64,
+    tag: String,
+    /// Host the request was fetched from
+    #[serde(skip)]
+    pub domain: String,
+}
+
+impl WithdrawRequest {
+    pub async fn fetch(url: Url) -> anyhow::Result<WithdrawRequest> {
+        let domain = url.host_str().unwrap_or_default().to_owned();
+        let mut withdraw_request = get_json::<WithdrawRequest>(url).await?;
+        if withdraw_request.tag != "withdrawRequest" {
+            anyhow::bail!(
+                "Expected a LNURL-withdraw request, got {}",
+                withdraw_request.tag
+            );
+        }
+        if withdraw_request.min_withdrawable > withdraw_request.max_withdrawable {
+            anyhow::bail!("{domain} returned an empty amount range");
+        }
+        withdraw_request.domain = domain;
+        Ok(withdraw_request)
+    }
+
+    /// Asks the service to pay `invoice`. The service pays asynchronously, so
+    /// success only means that it accepted the invoice.
+    pub async fn submit_invoice(&self, invoice: &str) -> anyhow::Result<()> {
+        let mut callback = Url::parse(&self.callback).context("Invalid callback URL")?;
+        callback
+            .query_pairs_mut()
+            .append_pair("k1", &self.k1)
+            .append_pair("pr", invoice);
+        get_json::<serde_json::Value>(callback).await?;
+        Ok(())
+    }
+}
+
+/// LUD-04 `login` request, which is fully contained in the LNURL itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthRequest {
+    url: Url,
+    pub domain: String,
+    pub k1: [u8; 32],
+    pub action: Option<String>,
+}
+
+impl AuthRequest {
+    /// Returns `None` if `url` isn't a LNURL-auth request
+    pub fn from_url(url: &Url) -> Option<AuthRequest> {
+        let query_value = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        };
+        if query_value("tag").as_deref() != Some("login") {
+            return None;
+        }
+        let k1 = hex::decode(query_value("k1")?).ok()?.try_into().ok()?;
+
+        Some(AuthRequest {
+            url: url.clone(),
+            domain: url.host_str()?.to_owned(),
+            k1,
+            action: query_value("action"),
+        })
+    }
+
+    /// What the user is asked to approve, based on the optional `action`
+    pub fn action_description(&self) -> &'static str {
+        match self.action.as_deref() {
+            Some("register") => "Create an account on",
+            Some("link") => "Link your existing account on",
+            Some("auth") => "Authorize an action on",
+            _ => "Log in to",
+        }
+    }
+
+    /// Sends the linking public `key` and the `sig`nature of `k1` to the
+    /// service.
+    pub async fn login(&self, key: &str, sig: &str) -> anyhow::Result<()> {
+        let mut callback = self.url.clone();
+        callback
+            .query_pairs_mut()
+            .append_pair("sig", sig)
+            .append_pair("key", key);
+        get_json::<serde_json::Value>(callback).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use bitcoin::secp256k1::{ecdsa, Message, PublicKey, Secp256k1, SecretKey};
+    use lightning_invoice::{Currency, Description, InvoiceBuilder, PaymentSecret, Sha256};
+
+    use super::*;
+
+    fn pay_request(min_sendable: u64, max_sendable: u64, comment_allowed: usize) -> PayRequest {
+        PayRequest {
+            callback: "https://service.com/callback".to_owned(),
+            min_sendable,
+            max_sendable,
+            metadata: r#"[["text/plain","Coffee"]]"#.to_owned(),
+            comment_allowed,
+            tag: "payRequest".to_owned(),
+            domain: "service.com".to_owned(),
+        }
+    }
+
+    fn parsed(input: &str) -> Option<String> {
+        parse(input).map(|url| url.to_string())
+    }
+
+    /// Stands in for a LNURL service on a local port, answering every request
+    /// with the JSON returned by `respond` for the requested URL. Returns the
+    /// service's base URL.
+    fn serve(respond: impl Fn(&Url) -> String + Send + 'static) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        let service_base = base.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let path = request_line.split(' ').nth(1).unwrap_or("/");
+                let body = respond(&service_base.join(path).unwrap());
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        base
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn query_value(url: &Url, name: &str) -> Option<String> {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    fn invoice(amount_msat: u64, description: &str) -> String {
+        let key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        InvoiceBuilder::new(Currency::Regtest)
+            .description_hash(sha256::Hash::hash(description.as_bytes()))
+            .payment_hash(sha256::Hash::hash(b"preimage"))
+            .payment_secret(PaymentSecret([0x22; 32]))
+            .duration_since_epoch(Duration::from_secs(1_700_000_000))
+            .min_final_cltv_expiry_delta(144)
+            .amount_milli_satoshis(amount_msat)
+            .build_signed(|hash| Secp256k1::new().sign_ecdsa_recoverable(hash, &key))
+            .unwrap()
+            .to_string()
+    }
+
+    fn pay_request_json(callback: &Url, tag: &str) -> String {
+        serde_json::json!({
+            "callback": callback,
+            "minSendable": 1_000,
+            "maxSendable": 10_000,
+            "metadata": r#"[["text/plain","Coffee"]]"#,
+            "commentAllowed": 10,
+            "tag": tag,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn parse_bech32() {
+        // Example from LUD-01
+        let lnurl = "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS";
+        let url = "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df";
+
+        assert_eq!(parsed(lnurl).as_deref(), Some(url));
+        assert_eq!(parsed(&lnurl.to_lowercase()).as_deref(), Some(url));
+        assert_eq!(parsed(&format!("lightning:{lnurl}")).as_deref(), Some(url));
+        assert_eq!(parsed(&lnurl[..lnurl.len() - 1]), None);
+    }
+
+    #[test]
+    fn parse_lightning_address() {
+        assert_eq!(
+            parsed("Alice@Example.com").as_deref(),
+            Some("https://example.com/.well-known/lnurlp/alice")
+        );
+        assert_eq!(
+            parsed(" bob.smith+tips@service.com ").as_deref(),
+            Some("https://service.com/.well-known/lnurlp/bob.smith+tips")
+        );
+        assert_eq!(
+            parsed("carol@abcdef.onion").as_deref(),
+            Some("http://abcdef.onion/.well-known/lnurlp/carol")
+        );
+        assert_eq!(parsed("@service.com"), None);
+        assert_eq!(parsed("alice@"), None);
+        assert_eq!(parsed("al ice@service.com"), None);
+        assert_eq!(parsed("alice@service.com/path"), None);
+    }
+
+    #[test]
+    fn parse_lud17_schemes() {
+        assert_eq!(
+            parsed("lnurlp://service.com/pay?q=1").as_deref(),
+            Some("https://service.com/pay?q=1")
+        );
+        assert_eq!(
+            parsed("LIGHTNING:LNURLW://service.com/withdraw").as_deref(),
+            Some("https://service.com/withdraw")
+        );
+        assert_eq!(
+            parsed("keyauth://service.com/login?tag=login").as_deref(),
+            Some("https://service.com/login?tag=login")
+        );
+        assert_eq!(
+            parsed("lnurlc://abcdef.onion/channel").as_deref(),
+            Some("http://abcdef.onion/channel")
+        );
+    }
+
+    #[test]
+    fn parse_rejects_other_input() {
+        assert_eq!(parsed("lnbc1500n1pj9rvfzpp5"), None);
+        assert_eq!(parsed("https://service.com"), None);
+        assert_eq!(parsed(""), None);
+    }
+
+    #[test]
+    fn amount_has_to_be_in_range() {
+        let request = pay_request(1_000, 2_000, 0);
+
+        assert!(request.check_request(999, None).is_err());
+        assert!(request.check_request(1_000, None).is_ok());
+        assert!(request.check_request(2_000, None).is_ok());
+        assert!(request.check_request(2_001, None).is_err());
+    }
+
+    #[test]
+    fn comment_length_is_limited() {
+        let request = pay_request(1_000, 1_000, 5);
+
+        assert_eq!(
+            request
+                .check_request(1_000, Some("ääääh".to_owned()))
+                .unwrap(),
+            Some("ääääh".to_owned())
+        );
+        assert!(request
+            .check_request(1_000, Some("123456".to_owned()))
+            .is_err());
+        assert_eq!(
+            request.check_request(1_000, Some(String::new())).unwrap(),
+            None
+        );
+
+        // Services that don't accept comments
+        let request = pay_request(1_000, 1_000, 0);
+        assert!(request.check_request(1_000, Some("a".to_owned())).is_err());
+        assert_eq!(request.check_request(1_000, None).unwrap(), None);
+    }
+
+    #[test]
+    fn description_has_to_commit_to_metadata() {
+        let request = pay_request(1_000, 1_000, 0);
+        let metadata_hash = Sha256(sha256::Hash::hash(request.metadata.as_bytes()));
+        let other_hash = Sha256(sha256::Hash::hash(b"other metadata"));
+        let description = Description::new(request.metadata.clone()).unwrap();
+
+        assert!(request
+            .check_description(Bolt11InvoiceDescription::Hash(&metadata_hash))
+            .is_ok());
+        assert!(request
+            .check_description(Bolt11InvoiceDescription::Hash(&other_hash))
+            .is_err());
+        assert!(request
+            .check_description(Bolt11InvoiceDescription::Direct(&description))
+            .is_err());
+    }
+
+    #[test]
+    fn aes_success_action_is_decrypted_with_the_preimage() {
+        let action = SuccessAction::Aes {
+            description: "Your code".to_owned(),
+            ciphertext: "GIFBDGV40d1ckXjM4NHssaqGAVIl6I/2CZ+CPODHs2w=".to_owned(),
+            iv: "IiIiIiIiIiIiIiIiIiIiIg==".to_owned(),
+        };
+
+        assert_eq!(
+            action.message(&"11".repeat(32)).unwrap(),
+            "Your code: secret code 1234"
+        );
+        assert!(action.message(&"12".repeat(32)).is_err());
+        assert!(action.message("not hex").is_err());
+    }
+
+    #[test]
+    fn url_success_action_has_to_match_the_domain() {
+        let action = |url: &str| SuccessAction::Url {
+            description: "Your receipt".to_owned(),
+            url: url.to_owned(),
+        };
+
+        assert!(action("https://service.com/receipt/1").is_issued_by("service.com"));
+        assert!(!action("https://evil.com/receipt/1").is_issued_by("service.com"));
+        assert!(!action("https://service.com.evil.com/").is_issued_by("service.com"));
+        assert!(!action("not a url").is_issued_by("service.com"));
+        assert!(SuccessAction::Message {
+            message: "Thanks".to_owned()
+        }
+        .is_issued_by("service.com"));
+    }
+
+    #[test]
+    fn fetch_pay_request() {
+        let base = serve(|url| match url.path() {
+            "/pay" => pay_request_json(&url.join("/callback").unwrap(), "payRequest"),
+            "/withdraw" => pay_request_json(&url.join("/callback").unwrap(), "withdrawRequest"),
+            "/empty" => serde_json::json!({
+                "callback": url.join("/callback").unwrap(),
+                "minSendable": 2_000,
+                "maxSendable": 1_000,
+                "metadata": "[]",
+                "tag": "payRequest",
+            })
+            .to_string(),
+            _ => r#"{"status":"ERROR","reason":"Unknown user"}"#.to_owned(),
+        });
+
+        let request = block_on(PayRequest::fetch(base.join("/pay").unwrap())).unwrap();
+        assert_eq!(request.callback, base.join("/callback").unwrap().as_str());
+        assert_eq!(
+            (request.min_sendable, request.max_sendable),
+            (1_000, 10_000)
+        );
+        assert_eq!(request.comment_allowed, 10);
+        assert_eq!(request.description().as_deref(), Some("Coffee"));
+        assert_eq!(request.domain, "127.0.0.1");
+
+        let error = block_on(PayRequest::fetch(base.join("/withdraw").unwrap())).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Expected a LNURL-pay request, got withdrawRequest"
+        );
+        let error = block_on(PayRequest::fetch(base.join("/empty").unwrap())).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "127.0.0.1 returned an empty amount range"
+        );
+        let error = block_on(PayRequest::fetch(base.join("/unknown").unwrap())).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "127.0.0.1 returned an error: Unknown user"
+        );
+    }
+
+    #[test]
+    fn request_invoice_from_service() {
+        let base = serve(|url| {
+            let request = pay_request(1_000, 10_000, 10);
+            let amount_msat = query_value(url, "amount").unwrap().parse::<u64>().unwrap();
+            let comment = query_value(url, "comment").unwrap_or_default();
+            match url.path() {
+                "/callback" => serde_json::json!({
+                    "pr": invoice(amount_msat, &request.metadata),
+                    "successAction": {"tag": "message", "message": format!("Thanks {comment}")},
+                }),
+                "/other-amount" => serde_json::json!({
+                    "pr": invoice(amount_msat + 1, &request.metadata),
+                }),
+                "/other-metadata" => serde_json::json!({
+                    "pr": invoice(amount_msat, "other metadata"),
+                }),
+                "/foreign-url" => serde_json::json!({
+                    "pr": invoice(amount_msat, &request.metadata),
+                    "successAction": {
+                        "tag": "url",
+                        "description": "Your receipt",
+                        "url": "https://evil.com/receipt",
+                    },
+                }),
+                _ => serde_json::json!({"status": "ERROR", "reason": "Route not found"}),
+            }
+            .to_string()
+        });
+        let request_at = |path: &str| PayRequest {
+            callback: base.join(path).unwrap().to_string(),
+            ..pay_request(1_000, 10_000, 10)
+        };
+
+        let response =
+            block_on(request_at("/callback").request_invoice(2_000, Some("Alice".to_owned())))
+                .unwrap();
+        assert_eq!(response.invoice, invoice(2_000, &request_at("/").metadata));
+        assert_eq!(
+            response.success_action,
+            Some(SuccessAction::Message {
+                message: "Thanks Alice".to_owned()
+            })
+        );
+
+        let response = block_on(request_at("/foreign-url").request_invoice(2_000, None)).unwrap();
+        assert_eq!(response.success_action, None);
+
+        let error = block_on(request_at("/other-amount").request_invoice(2_000, None)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The invoice returned by the service is for a different amount"
+        );
+        let error =
+            block_on(request_at("/other-metadata").request_invoice(2_000, None)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The invoice returned by the service doesn't match its metadata"
+        );
+        let error = block_on(request_at("/missing").request_invoice(2_000, None)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "127.0.0.1 returned an error: Route not found"
+        );
+
+        // Invalid requests don't reach the service
+        assert!(block_on(request_at("/callback").request_invoice(20_000, None)).is_err());
+    }
+
+    #[test]
+    fn parse_auth_request() {
+        let k1 = "ab".repeat(32);
+        let url = |query: &str| Url::parse(&format!("https://site.com/auth?{query}")).unwrap();
+
+        let request = AuthRequest::from_url(&url(&format!("tag=login&k1={k1}"))).unwrap();
+        assert_eq!(request.domain, "site.com");
+        assert_eq!(request.k1, [0xab; 32]);
+        assert_eq!(request.action_description(), "Log in to");
+
+        let request =
+            AuthRequest::from_url(&url(&format!("tag=login&k1={k1}&action=register"))).unwrap();
+        assert_eq!(request.action_description(), "Create an account on");
+
+        assert_eq!(AuthRequest::from_url(&url(&format!("k1={k1}"))), None);
+        assert_eq!(AuthRequest::from_url(&url("tag=login&k1=abab")), None);
+        assert_eq!(AuthRequest::from_url(&url("tag=login&k1=not-hex")), None);
+    }
+
+    #[test]
+    fn login_is_verified_by_service() {
+        // Accepts logins with a valid signature of `k1` by `key`, like a
+        // LNURL-auth service would
+        let base = serve(|url| {
+            let verified = (|| {
+                let k1 = hex::decode(query_value(url, "k1")?).ok()?;
+                let sig = hex::decode(query_value(url, "sig")?).ok()?;
+                let key = hex::decode(query_value(url, "key")?).ok()?;
+                Secp256k1::new()
+                    .verify_ecdsa(
+                        &Message::from_slice(&k1).ok()?,
+                        &ecdsa::Signature::from_der(&sig).ok()?,
+                        &PublicKey::from_slice(&key).ok()?,
+                    )
+                    .ok()
+            })();
+            match verified {
+                Some(()) => r#"{"status":"OK"}"#.to_owned(),
+                None => r#"{"status":"ERROR","reason":"Invalid signature"}"#.to_owned(),
+            }
+        });
+        let mut url = base.join("/auth").unwrap();
+        url.query_pairs_mut()
+            .append_pair("tag", "login")
+            .append_pair("k1", &"2a".repeat(32));
+        let request = AuthRequest::from_url(&url).unwrap();
+
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let key = hex::encode(PublicKey::from_secret_key(&secp, &secret_key).serialize());
+        let sign = |message: [u8; 32]| {
+            let message = Message::from_slice(&message).unwrap();
+            hex::encode(secp.sign_ecdsa(&message, &secret_key).serialize_der())
+        };
+
+        block_on(request.login(&key, &sign(request.k1))).unwrap();
+
+        let error = block_on(request.login(&key, &sign([0x2b; 32]))).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "127.0.0.1 returned an error: Invalid signature"
+        );
+    }
+}
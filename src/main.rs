@@ -1,5 +1,6 @@
 mod client;
 mod db;
+mod lnurl;
 
 mod components;
 mod context;
@@ -0,0 +1,220 @@
+use std::time::Duration;
+
+use fedimint_core::module::__reexports::serde_json;
+use leptos::{document, set_timeout};
+use serde::Serialize;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+use crate::client::Transaction;
+use crate::utils::format_rfc3339;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "webimint-transactions.csv",
+            ExportFormat::Json => "webimint-transactions.json",
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn export(self, transactions: &[Transaction]) -> String {
+        let records = transactions
+            .iter()
+            .map(TransactionRecord::from)
+            .collect::<Vec<_>>();
+        match self {
+            ExportFormat::Csv => records_to_csv(&records),
+            ExportFormat::Json => serde_json::to_string_pretty(&records)
+                .expect("Serializing transaction records can't fail"),
+        }
+    }
+}
+
+/// Flat representation of a [`Transaction`] with stable, human readable
+/// values meant for reconciliation in spreadsheets or accounting tools.
+#[derive(Debug, Serialize)]
+struct TransactionRecord {
+    timestamp: String,
+    operation_id: String,
+    kind: String,
+    direction: String,
+    amount_msat: i64,
+    fee_msat: u64,
+    status: String,
+    description: String,
+    label: String,
+    tags: Vec<String>,
+}
+
+impl From<&Transaction> for TransactionRecord {
+    fn from(transaction: &Transaction) -> Self {
+        TransactionRecord {
+            timestamp: format_rfc3339(transaction.timestamp),
+            operation_id: hex::encode(transaction.operation_id.0),
+            kind: transaction.operation_kind.clone(),
+            direction: transaction.direction.to_string(),
+            amount_msat: transaction.amount_msat,
+            fee_msat: transaction.fee_msat,
+            status: transaction.status.to_string(),
+            description: transaction.description.clone().unwrap_or_default(),
+            label: transaction.label.clone().unwrap_or_default(),
+            tags: transaction.tags.clone(),
+        }
+    }
+}
+
+fn records_to_csv(records: &[TransactionRecord]) -> String {
+    let mut csv = String::from(
+        "timestamp,operation_id,kind,direction,amount_msat,fee_msat,status,description,label,tags\n",
+    );
+    for record in records {
+        let fields = [
+            record.timestamp.clone(),
+            record.operation_id.clone(),
+            record.kind.clone(),
+            record.direction.clone(),
+            record.amount_msat.to_string(),
+            record.fee_msat.to_string(),
+            record.status.clone(),
+            neutralize_formula(&record.description),
+            neutralize_formula(&record.label),
+            neutralize_formula(&record.tags.join(";")),
+        ];
+        let line = fields
+            .iter()
+            .map(|field| csv_escape(field))
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push_str(&line);
+        csv.push('\n');
+    }
+    csv
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Descriptions are chosen by third parties and labels might be pasted from
+/// anywhere, so keep spreadsheet applications
+/// from interpreting them as formulas.
+fn neutralize_formula(field: &str) -> String {
+    if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{field}")
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Lets the browser download `content` as a file called `file_name`.
+pub fn download_file(file_name: &str, mime_type: &str, content: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(content));
+    let mut options = BlobPropertyBag::new();
+    options.type_(mime_type);
+    let blob = Blob::new_with_str_sequence_and_options(&parts, &options)?;
+
+    let url = Url::create_object_url_with_blob(&blob)?;
+    let anchor = document()
+        .create_element("a")?
+        .unchecked_into::<HtmlAnchorElement>();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    // Some browsers, e.g. Firefox, only start reading the blob after the click
+    // handler returned and cancel the download if it was revoked by then
+    set_timeout(
+        move || {
+            let _ = Url::revoke_object_url(&url);
+        },
+        Duration::from_secs(1),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use fedimint_core::core::OperationId;
+
+    use super::*;
+    use crate::client::{TransactionDirection, TransactionStatus};
+
+    fn transaction(description: &str, label: &str, tags: &[&str]) -> Transaction {
+        Transaction {
+            timestamp: UNIX_EPOCH + Duration::from_secs(1_706_720_742),
+            operation_id: OperationId([0xab; 32]),
+            operation_kind: "ln".to_owned(),
+            direction: TransactionDirection::Outgoing,
+            amount_msat: -1_000,
+            fee_msat: 10,
+            status: TransactionStatus::Success,
+            description: Some(description.to_owned()),
+            label: Some(label.to_owned()),
+            tags: tags.iter().map(|tag| (*tag).to_owned()).collect(),
+            reclaim_deadline: None,
+        }
+    }
+
+    #[test]
+    fn csv_has_header_and_one_line_per_transaction() {
+        let csv = ExportFormat::Csv.export(&[transaction("coffee", "", &[])]);
+        let lines = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "timestamp,operation_id,kind,direction,amount_msat,fee_msat,status,description,label,tags"
+        );
+        assert_eq!(
+            lines[1],
+            format!(
+                "2024-01-31T17:05:42Z,{},ln,out,-1000,10,success,coffee,,",
+                "ab".repeat(32)
+            )
+        );
+    }
+
+    #[test]
+    fn csv_escapes_separators_and_quotes() {
+        let csv = ExportFormat::Csv.export(&[transaction("a, \"b\"", "line\nbreak", &["x", "y"])]);
+
+        assert!(csv.contains(",\"a, \"\"b\"\"\",\"line\nbreak\",x;y\n"));
+    }
+
+    #[test]
+    fn csv_neutralizes_formulas() {
+        let csv = ExportFormat::Csv.export(&[transaction("=HYPERLINK(1)", "+1", &["@tag"])]);
+
+        assert!(csv.ends_with(",'=HYPERLINK(1),'+1,'@tag\n"));
+    }
+
+    #[test]
+    fn json_keeps_raw_values() {
+        let json = ExportFormat::Json.export(&[transaction("=1", "label", &["a", "b"])]);
+        let records: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(records[0]["timestamp"], "2024-01-31T17:05:42Z");
+        assert_eq!(records[0]["amount_msat"], -1000);
+        assert_eq!(records[0]["description"], "=1");
+        assert_eq!(records[0]["tags"], serde_json::json!(["a", "b"]));
+    }
+}
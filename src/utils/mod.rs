@@ -1,2 +1,8 @@
+pub mod export;
+pub mod pending_redemption;
+pub mod time;
 pub mod view;
+pub use export::*;
+pub use pending_redemption::*;
+pub use time::*;
 pub use view::*;
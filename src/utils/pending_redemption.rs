@@ -0,0 +1,46 @@
+use anyhow::anyhow;
+use gloo_storage::{SessionStorage, Storage};
+use leptos::window;
+use serde::{Deserialize, Serialize};
+
+use crate::db::PersistentMemDb;
+
+const PENDING_REDEMPTION_KEY: &str = "webimint-pending-redemption";
+
+/// E-cash notes of another federation to redeem into a new wallet that joins
+/// it. Only one wallet can be opened per page load, so the redemption is kept
+/// in the session storage while the app reloads to create the new wallet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRedemption {
+    pub wallet_name: String,
+    pub invite_code: String,
+    pub notes: String,
+}
+
+impl PendingRedemption {
+    /// Remembers the redemption and reloads the app, which then opens the new
+    /// wallet
+    pub fn start(&self) -> anyhow::Result<()> {
+        // Wallets live in the local storage, unlike the pending redemption
+        if PersistentMemDb::list_dbs().contains(&self.wallet_name) {
+            anyhow::bail!("A wallet named \"{}\" already exists", self.wallet_name);
+        }
+        SessionStorage::set(PENDING_REDEMPTION_KEY, self)?;
+        window()
+            .location()
+            .reload()
+            .map_err(|e| anyhow!("Failed to reload the app: {e:?}"))
+    }
+
+    /// Returns the redemption started before the app reloaded. It is kept
+    /// until [`PendingRedemption::clear`] is called, so the notes survive
+    /// another reload before they were redeemed.
+    pub fn get() -> Option<PendingRedemption> {
+        SessionStorage::get(PENDING_REDEMPTION_KEY).ok()
+    }
+
+    /// Forgets the pending redemption once the notes are being redeemed
+    pub fn clear() {
+        SessionStorage::delete(PENDING_REDEMPTION_KEY);
+    }
+}
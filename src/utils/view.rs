@@ -1,5 +1,20 @@
+use std::time::{Duration, SystemTime};
+
+use fedimint_core::time::now;
 use leptos::*;
 
 pub fn empty_view() -> impl IntoView {
     view! { "" }
 }
+
+/// Signal holding the current time, updated every second for as long as the
+/// calling component is mounted. Used to drive countdowns.
+pub fn create_clock() -> ReadSignal<SystemTime> {
+    let (time, set_time) = create_signal(now());
+    if let Ok(handle) =
+        set_interval_with_handle(move || set_time.set(now()), Duration::from_secs(1))
+    {
+        on_cleanup(move || handle.clear());
+    }
+    time
+}